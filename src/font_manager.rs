@@ -10,6 +10,12 @@ pub struct FontConfig {
     pub font_path: Option<String>,
 }
 
+/// 实际加载的默认字体（path 为 None 表示使用 Bevy 内置字体）
+#[derive(Resource, Debug, Clone, Default)]
+pub struct LoadedFont {
+    pub path: Option<String>,
+}
+
 /// 获取系统默认字体路径列表（按优先级排序）
 fn get_default_font_paths() -> Vec<&'static str> {
     if cfg!(target_os = "windows") {
@@ -83,6 +89,7 @@ pub fn load_and_set_default_font(world: &mut World, config: &FontConfig) -> bool
 
     // 所有字体加载都失败，使用 Bevy 默认字体
     warn!("使用 Bevy 内置默认字体");
+    world.insert_resource(LoadedFont::default());
     false
 }
 
@@ -92,6 +99,9 @@ fn set_default_font(world: &mut World, font: Font, path: &str) {
     let _ = world
         .resource_mut::<Assets<Font>>()
         .insert(&default_font_handle, font);
+    world.insert_resource(LoadedFont {
+        path: Some(path.to_string()),
+    });
     info!("已设置默认字体: {}", path);
 }
 
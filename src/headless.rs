@@ -0,0 +1,47 @@
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::camera::RenderTarget;
+use bevy::prelude::*;
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use log::info;
+use std::time::Duration;
+
+/// 离屏视口宽度（与窗口模式的 800x600 保持一致）
+pub const VIEWPORT_WIDTH: u32 = 800;
+/// 离屏视口高度
+pub const VIEWPORT_HEIGHT: u32 = 600;
+
+/// 离屏渲染目标：headless 模式下相机渲染到此 Image，截图也从这里读取
+#[derive(Resource, Clone)]
+pub struct OffscreenTarget(pub Handle<Image>);
+
+impl OffscreenTarget {
+    pub fn render_target(&self) -> RenderTarget {
+        RenderTarget::Image(self.0.clone().into())
+    }
+}
+
+/// 无窗口模式插件
+///
+/// 用 ScheduleRunnerPlugin 代替 winit 驱动主循环，并在 PreStartup 创建离屏渲染目标。
+/// 需要配合禁用 WinitPlugin、`primary_window: None` 的 DefaultPlugins 使用。
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        .add_systems(PreStartup, create_offscreen_target);
+    }
+}
+
+fn create_offscreen_target(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let mut image = Image::new_target_texture(
+        VIEWPORT_WIDTH,
+        VIEWPORT_HEIGHT,
+        TextureFormat::bevy_default(),
+    );
+    image.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    commands.insert_resource(OffscreenTarget(images.add(image)));
+    info!("离屏渲染目标: {}x{}", VIEWPORT_WIDTH, VIEWPORT_HEIGHT);
+}
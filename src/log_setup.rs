@@ -1,15 +1,20 @@
-use log::LevelFilter;
+use log::{LevelFilter, Record};
 use std::env;
 
+use crate::test_system::mcp::forward_log;
+
 /// 初始化日志系统
 ///
 /// 支持两种模式：
 /// - 测试模式：根据 TEST_LOG_FILE 环境变量配置独立日志文件
 /// - 正常模式：使用 log4rs.yaml 配置文件
+///
+/// `--mcp-stdio` 时 stdout 是协议流，不能有控制台输出，总是使用测试模式的配置（只写日志文件）
 pub fn init_logging() {
     let log_file = env::var("TEST_LOG_FILE").unwrap_or_else(|_| "logs/game.log".to_string());
+    let stdio = env::args().any(|arg| arg == "--mcp-stdio");
 
-    if env::var("TEST_LOG_FILE").is_ok() {
+    if stdio || env::var("TEST_LOG_FILE").is_ok() {
         setup_test_logging(&log_file);
     } else {
         setup_normal_logging();
@@ -18,6 +23,8 @@ pub fn init_logging() {
 
 /// 测试模式日志配置
 ///
+/// 业务日志同时推送给 MCP 客户端（notifications/message，级别由 logging/setLevel 控制）。
+///
 /// 创建两个日志文件：
 /// - 主日志：只记录业务日志（2KB 左右）
 /// - Debug 日志：根据 TEST_DEBUG 环境变量决定级别
@@ -65,11 +72,14 @@ fn setup_test_logging(log_file: &str) {
                 .filter(Box::new(ThresholdFilter::new(debug_level)))
                 .build("debug_file", Box::new(debug_appender)),
         )
+        // MCP 日志通知
+        .appender(Appender::builder().build("mcp", Box::new(McpAppender)))
         // 我们的应用日志：输出到主日志
         .logger(
             Logger::builder()
                 .appender("file")
                 .appender("debug_file")
+                .appender("mcp")
                 .additive(false)
                 .build("simple_game", LevelFilter::Info),
         )
@@ -78,6 +88,7 @@ fn setup_test_logging(log_file: &str) {
             Logger::builder()
                 .appender("file")
                 .appender("debug_file")
+                .appender("mcp")
                 .additive(false)
                 .build("test_system", LevelFilter::Info),
         )
@@ -92,3 +103,16 @@ fn setup_test_logging(log_file: &str) {
 fn setup_normal_logging() {
     log4rs::init_file("log4rs.yaml", Default::default()).expect("Failed to initialize log4rs");
 }
+
+/// 把日志转发给已连接的 MCP 客户端
+#[derive(Debug)]
+struct McpAppender;
+
+impl log4rs::append::Append for McpAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        forward_log(record.level(), record.target(), record.args().to_string());
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
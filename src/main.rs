@@ -1,9 +1,14 @@
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
 use log::info;
 use std::env;
 
+use test_system::RegisterTestTool;
+
 mod font_manager;
+mod headless;
 mod log_setup;
 mod test_system;
 
@@ -19,43 +24,73 @@ fn main() {
         info!("日志文件: {}", log);
     }
 
-    // 检查是否为测试模式
-    let test_mode = env::args().any(|arg| arg == "--test-mode");
-    if test_mode {
+    // 检查是否为测试模式；--mcp-stdio 同样启用测试模式，但以 stdin/stdout 代替 HTTP 提供 MCP
+    let stdio = env::args().any(|arg| arg == "--mcp-stdio");
+    if stdio {
+        info!("测试模式已启用（MCP stdio）");
+        test_system::start_stdio_server();
+    } else if env::args().any(|arg| arg == "--test-mode") {
         info!("测试模式已启用");
         test_system::start_test_server();
     }
+    if env::args().any(|arg| arg == "--update-baselines") {
+        info!("基准图更新模式已启用");
+    }
+
+    // 检查是否为无窗口模式（相机渲染到离屏 Image，无需 X server）
+    let headless = env::args().any(|arg| arg == "--headless");
 
     let mut app = App::new();
 
     // 禁用 Bevy 的 LogPlugin，使用我们自己的 log4rs
-    app.add_plugins(
-        DefaultPlugins
-            .set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "简单游戏".to_string(),
-                    resolution: (800, 600).into(),
+    if headless {
+        info!("无窗口模式已启用");
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
                     ..default()
-                }),
-                ..default()
-            })
-            .build()
-            .disable::<LogPlugin>(),
-    );
+                })
+                .build()
+                .disable::<LogPlugin>()
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugins(headless::HeadlessPlugin);
+    } else {
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "简单游戏".to_string(),
+                        resolution: (800, 600).into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .build()
+                .disable::<LogPlugin>(),
+        );
+    }
 
     // 从系统加载中文字体作为默认字体
     let font_config = font_manager::FontConfig::default();
     font_manager::load_and_set_default_font(app.world_mut(), &font_config);
 
-    app.add_systems(Startup, setup)
-        .add_systems(
-            Update,
-            (
-                test_system::receive_test_messages,
-                handle_button_interaction.after(test_system::receive_test_messages),
-                update_button_visuals,
-            ),
+    app.add_plugins(test_system::TestSystemPlugin)
+        .register_test_tool(
+            "spawn_balls",
+            "直接生成 N 个随机位置的小球（不经过按钮），返回生成数量与当前小球总数",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "count": { "type": "integer", "minimum": 1, "maximum": 100, "default": 1, "description": "生成数量" }
+                }
+            }),
+            spawn_balls,
         )
+        .add_systems(Startup, setup)
+        .add_systems(Update, (handle_button_interaction, update_button_visuals))
         .run();
 }
 
@@ -79,34 +114,47 @@ fn handle_button_interaction(
             info!("test-id-button-clicked: main-button");
             info!("按钮被点击!");
 
-            // 生成随机位置的小球
-            use rand::Rng;
-            let mut rng = rand::thread_rng();
-            let x = rng.gen_range(-300.0..300.0);
-            let y = rng.gen_range(-200.0..200.0);
-
-            commands.spawn((
-                Node {
-                    position_type: PositionType::Absolute,
-                    left: Val::Px(400.0 + x),
-                    top: Val::Px(300.0 + y),
-                    width: Val::Px(30.0),
-                    height: Val::Px(30.0),
-                    border: UiRect::all(Val::Px(2.0)),
-                    ..default()
-                },
-                BorderColor::all(Color::srgb(1.0, 1.0, 1.0)),
-                BackgroundColor(Color::srgb(1.0, 0.3, 0.3)),
-                BorderRadius::all(Val::Percent(50.0)),
-                Ball,
-                TestId("ball".to_string()),
-            ));
-
-            info!("生成小球在位置: ({}, {})", x, y);
+            commands.spawn(random_ball());
         }
     }
 }
 
+// 随机位置的小球
+fn random_ball() -> impl Bundle {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let x = rng.gen_range(-300.0..300.0);
+    let y = rng.gen_range(-200.0..200.0);
+    info!("生成小球在位置: ({}, {})", x, y);
+
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(400.0 + x),
+            top: Val::Px(300.0 + y),
+            width: Val::Px(30.0),
+            height: Val::Px(30.0),
+            border: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        BorderColor::all(Color::srgb(1.0, 1.0, 1.0)),
+        BackgroundColor(Color::srgb(1.0, 0.3, 0.3)),
+        BorderRadius::all(Val::Percent(50.0)),
+        Ball,
+        TestId("ball".to_string()),
+    )
+}
+
+// 游戏自定义测试工具：直接生成小球，不经过按钮
+fn spawn_balls(world: &mut World, args: serde_json::Value) -> Result<serde_json::Value, String> {
+    let count = args["count"].as_u64().unwrap_or(1);
+    for _ in 0..count {
+        world.spawn(random_ball());
+    }
+    let total = world.query_filtered::<(), With<Ball>>().iter(world).count();
+    Ok(serde_json::json!({ "spawned": count, "total": total }))
+}
+
 // 更新按钮视觉状态（响应 Interaction 变化）
 #[allow(clippy::type_complexity)]
 fn update_button_visuals(
@@ -132,9 +180,18 @@ fn update_button_visuals(
     }
 }
 
-fn setup(mut commands: Commands) {
-    // 相机
-    commands.spawn(Camera2d);
+fn setup(mut commands: Commands, offscreen: Option<Res<headless::OffscreenTarget>>) {
+    // 相机（无窗口模式下渲染到离屏 Image）
+    match offscreen {
+        Some(target) => commands.spawn((
+            Camera2d,
+            Camera {
+                target: target.render_target(),
+                ..default()
+            },
+        )),
+        None => commands.spawn(Camera2d),
+    };
 
     // 根节点 - 居中容器
     commands
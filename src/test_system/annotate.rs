@@ -0,0 +1,141 @@
+//! 标注截图（set-of-marks）：为可交互节点绘制编号框与 testId / uid 标签
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{Rgb, RgbImage};
+use std::sync::Arc;
+
+use crate::test_system::channel::AnnotationMark;
+
+/// 标签字号（像素）
+const LABEL_SIZE: f32 = 14.0;
+/// 边框粗细（像素）
+const OUTLINE_WIDTH: i32 = 2;
+/// 编号框颜色，按编号循环使用
+const PALETTE: [[u8; 3]; 6] = [
+    [230, 40, 40],
+    [30, 160, 60],
+    [40, 90, 230],
+    [220, 140, 0],
+    [170, 40, 200],
+    [0, 160, 170],
+];
+
+/// 截图时的标注数据：编号框与绘制标签用的字体
+pub struct Annotation {
+    pub marks: Vec<AnnotationMark>,
+    /// font_manager 加载的默认字体数据，缺失时只画框不画标签
+    pub font: Option<Arc<Vec<u8>>>,
+}
+
+/// 在图像上绘制编号框；scale 为图像相对整帧物理像素的缩放比例
+pub fn draw(img: &mut RgbImage, annotation: &Annotation, scale: f32) {
+    let font = annotation
+        .font
+        .as_deref()
+        .and_then(|data| FontRef::try_from_slice(data).ok());
+    for mark in &annotation.marks {
+        let color = Rgb(PALETTE[(mark.number as usize).saturating_sub(1) % PALETTE.len()]);
+        let x0 = (mark.x * scale).floor() as i32;
+        let y0 = (mark.y * scale).floor() as i32;
+        let x1 = ((mark.x + mark.width) * scale).ceil() as i32;
+        let y1 = ((mark.y + mark.height) * scale).ceil() as i32;
+        for i in 0..OUTLINE_WIDTH {
+            fill_rect(img, x0, y0 + i, x1, y0 + i + 1, color);
+            fill_rect(img, x0, y1 - i - 1, x1, y1 - i, color);
+            fill_rect(img, x0 + i, y0, x0 + i + 1, y1, color);
+            fill_rect(img, x1 - i - 1, y0, x1 - i, y1, color);
+        }
+
+        let Some(font) = &font else {
+            continue;
+        };
+        let label = format!(
+            "{} {}",
+            mark.number,
+            mark.test_id.as_deref().unwrap_or(&mark.uid)
+        );
+        let scaled = font.as_scaled(PxScale::from(LABEL_SIZE));
+        let text_width: f32 = label
+            .chars()
+            .map(|c| scaled.h_advance(font.glyph_id(c)))
+            .sum();
+        let badge_height = scaled.height().ceil() as i32 + 2;
+        // 标签放在框的上方，顶部空间不足时放进框内
+        let badge_y = if y0 >= badge_height {
+            y0 - badge_height
+        } else {
+            y0
+        };
+        let badge_x1 = x0 + text_width.ceil() as i32 + 6;
+        fill_rect(img, x0, badge_y, badge_x1, badge_y + badge_height, color);
+        draw_text(img, font, &label, x0 + 3, badge_y + 1, Rgb([255, 255, 255]));
+    }
+}
+
+/// 填充矩形 [x0, x1) × [y0, y1)，超出图像的部分忽略
+fn fill_rect(img: &mut RgbImage, x0: i32, y0: i32, x1: i32, y1: i32, color: Rgb<u8>) {
+    let (width, height) = (img.width() as i32, img.height() as i32);
+    for y in y0.max(0)..y1.min(height) {
+        for x in x0.max(0)..x1.min(width) {
+            img.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// 以 (x, y) 为左上角绘制单行文字（按覆盖率与背景混合）
+fn draw_text(img: &mut RgbImage, font: &FontRef, text: &str, x: i32, y: i32, color: Rgb<u8>) {
+    let scale = PxScale::from(LABEL_SIZE);
+    let scaled = font.as_scaled(scale);
+    let mut caret = x as f32;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        let glyph = id.with_scale_and_position(scale, point(caret, y as f32 + scaled.ascent()));
+        caret += scaled.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= img.width() as i32 || py >= img.height() as i32 {
+                return;
+            }
+            let pixel = img.get_pixel_mut(px as u32, py as u32);
+            for c in 0..3 {
+                let blended = pixel[c] as f32 * (1.0 - coverage) + color[c] as f32 * coverage;
+                pixel[c] = blended.round() as u8;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_outline_without_font() {
+        let mut img = RgbImage::new(100, 80);
+        let annotation = Annotation {
+            marks: vec![AnnotationMark {
+                number: 1,
+                uid: "bits:1".to_string(),
+                test_id: Some("main-button".to_string()),
+                name: "bits:1".to_string(),
+                text: None,
+                x: 20.0,
+                y: 20.0,
+                width: 40.0,
+                height: 30.0,
+            }],
+            font: None,
+        };
+        draw(&mut img, &annotation, 0.5);
+        // 缩放后矩形为 (10, 10) - (30, 25)
+        assert_eq!(img.get_pixel(10, 10).0, PALETTE[0]);
+        assert_eq!(img.get_pixel(29, 24).0, PALETTE[0]);
+        assert_eq!(img.get_pixel(20, 17).0, [0, 0, 0]);
+        assert_eq!(img.get_pixel(40, 40).0, [0, 0, 0]);
+    }
+}
@@ -1,230 +1,408 @@
+use bevy::diagnostic::FrameCount;
 use bevy::prelude::*;
 use log::info;
-
-use crate::test_system::channel::{LogEntryData, TestMessage, UINodeData, TEST_COMMAND_CHANNEL};
+use std::sync::atomic::Ordering;
+use tokio::sync::oneshot;
+
+use crate::test_system::annotate::Annotation;
+use crate::test_system::channel::{
+    ActionError, AnnotationMark, LogEntryData, ProbeTarget, ScreenshotError, Settle, TestMessage,
+    TestStage, UINodeData, CURRENT_FRAME, TEST_COMMAND_CHANNEL,
+};
+use crate::test_system::highlight::{self, TestOverlay};
+use crate::test_system::{cursor, custom_tools, environment, recording, screenshot};
 use crate::{Ball, GameButton, TestId};
 
-// 从消息队列接收测试消息并直接处理
+/// 等待中的帧同步屏障（登记帧号、需等待帧数、响应通道）
+#[derive(Resource, Default)]
+pub struct PendingSettles(Vec<(u32, u32, oneshot::Sender<u32>)>);
+
+/// 按执行阶段暂存的测试消息，由各阶段系统依次取出处理
+#[derive(Resource, Default)]
+pub struct StagedTestMessages {
+    interaction: Vec<TestMessage>,
+    query: Vec<TestMessage>,
+}
+
+// 从消息队列接收测试消息：输入类消息立即注入，其余按阶段暂存
 pub fn receive_test_messages(
-    mut commands: Commands,
-    mut button_query: Query<&mut Interaction, With<GameButton>>,
-    ball_query: Query<&Ball>,
-    button_count_query: Query<&GameButton>,
     mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    frame_count: Res<FrameCount>,
+    mut pending_settles: ResMut<PendingSettles>,
+    mut staged: ResMut<StagedTestMessages>,
 ) {
-    if let Some(channel) = TEST_COMMAND_CHANNEL.get() {
-        // 非阻塞地接收所有待处理消息
-        while let Ok(msg) = channel.receiver.try_recv() {
-            match msg {
-                // ---- 原有消息 ----
-                TestMessage::Hover { x, y, response } => {
-                    info!("收到测试悬停消息: ({}, {})", x, y);
-                    for mut interaction in button_query.iter_mut() {
-                        *interaction = Interaction::Hovered;
-                    }
-                    let _ = response.send(true);
-                }
-                TestMessage::Click { x, y, response } => {
-                    info!("收到测试点击消息: ({}, {})", x, y);
-                    for mut interaction in button_query.iter_mut() {
-                        *interaction = Interaction::Pressed;
-                    }
-                    let _ = response.send(true);
-                }
-                TestMessage::Screenshot { path, response } => {
-                    info!("收到截图请求: {}", path);
-                    let path_clone = path.clone();
-
-                    commands
-                        .spawn(bevy::render::view::screenshot::Screenshot::primary_window())
-                        .observe(bevy::render::view::screenshot::save_to_disk(path));
-
-                    std::thread::spawn(move || {
-                        use backoff::ExponentialBackoffBuilder;
-                        use std::time::Duration;
-
-                        let backoff_config = ExponentialBackoffBuilder::new()
-                            .with_initial_interval(Duration::from_millis(50))
-                            .with_max_interval(Duration::from_millis(500))
-                            .with_max_elapsed_time(Some(Duration::from_secs(5)))
-                            .build();
-
-                        let result = backoff::retry(backoff_config, || {
-                            let path = std::path::Path::new(&path_clone);
-                            if path.exists() {
-                                // 等文件写入完成（size > 0），避免 base64 读到空数据
-                                match std::fs::metadata(&path_clone) {
-                                    Ok(m) if m.len() > 0 => Ok(()),
-                                    _ => Err(backoff::Error::transient("文件未写完")),
-                                }
-                            } else {
-                                Err(backoff::Error::transient("文件未生成"))
-                            }
-                        });
+    let Some(channel) = TEST_COMMAND_CHANNEL.get() else {
+        return;
+    };
+    CURRENT_FRAME.store(frame_count.0, Ordering::Relaxed);
+    // 非阻塞地接收所有待处理消息
+    while let Ok(msg) = channel.receiver.try_recv() {
+        if msg.is_cancelled() {
+            info!("跳过已取消的测试消息: {:?}", msg.stage());
+            continue;
+        }
+        match msg.stage() {
+            TestStage::Input => {}
+            TestStage::Interaction => {
+                staged.interaction.push(msg);
+                continue;
+            }
+            TestStage::Query => {
+                staged.query.push(msg);
+                continue;
+            }
+        }
+        match msg {
+            // ---- 键盘 / 文本输入 ----
+            TestMessage::PressKey { key, response } => {
+                info!("收到 PressKey: {}", key);
+                let result = if let Some(key_code) = parse_key_code(&key) {
+                    keyboard_input.press(key_code);
+                    info!("PressKey 成功: {:?}", key_code);
+                    Ok(())
+                } else {
+                    info!("未知按键名称: {}", key);
+                    Err(ActionError::InvalidArgument(format!(
+                        "未知按键名称: {}",
+                        key
+                    )))
+                };
+                let _ = response.send(result);
+            }
 
-                        let _ = response.send(result.is_ok());
+            // ---- 日志 / 脚本 ----
+            TestMessage::GetLogs {
+                lines,
+                log_file,
+                response,
+            } => {
+                info!("收到 GetLogs: lines={}", lines);
+                std::thread::spawn(move || {
+                    let file_path = log_file.unwrap_or_else(|| {
+                        std::env::var("TEST_LOG_FILE")
+                            .unwrap_or_else(|_| "logs/game.log".to_string())
                     });
+                    let entries = read_log_file(&file_path, lines);
+                    let _ = response.send(entries);
+                });
+            }
+            TestMessage::EvaluateScript { script, response } => {
+                info!("收到 EvaluateScript");
+                std::thread::spawn(move || {
+                    // 若设置了 JS_EVALUATOR_URL，向 Tauri IPC 端点 POST 脚本
+                    if let Ok(url) = std::env::var("JS_EVALUATOR_URL") {
+                        // 简单 HTTP POST（需要游戏侧有对应端点）
+                        let result = std::process::Command::new("curl")
+                            .args([
+                                "-s",
+                                "-X",
+                                "POST",
+                                "-H",
+                                "Content-Type: application/json",
+                                "-d",
+                                &format!(
+                                    "{{\"script\":{}}}",
+                                    serde_json::to_string(&script).unwrap_or_default()
+                                ),
+                                &url,
+                            ])
+                            .output()
+                            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+                            .unwrap_or_else(|e| format!("{{\"error\":\"curl 失败: {}\"}}", e));
+                        let _ = response.send(result);
+                    } else {
+                        let _ = response.send(
+                            "{\"error\":\"未配置 JS_EVALUATOR_URL 环境变量，Tauri 集成需要设置此变量\"}".to_string()
+                        );
+                    }
+                });
+            }
+
+            // ---- 帧同步 ----
+            TestMessage::Settle { settle, response } => {
+                if settle == Settle::Immediate {
+                    let _ = response.send(frame_count.0);
+                } else {
+                    pending_settles
+                        .0
+                        .push((frame_count.0, settle.frames_to_wait(), response));
                 }
-                TestMessage::QueryComponents { response } => {
-                    info!("收到组件查询消息");
-                    let ball_count = ball_query.iter().count();
-                    let button_count = button_count_query.iter().count();
-
-                    let mut counts = std::collections::HashMap::new();
-                    counts.insert("Ball".to_string(), ball_count);
-                    counts.insert("Button".to_string(), button_count);
-
-                    info!(
-                        "COMPONENT_COUNTS: Ball={}, Button={}",
-                        ball_count, button_count
-                    );
-                    let _ = response.send(counts);
-                }
+            }
 
-                // ---- CDP 风格：UI 快照 ----
-                TestMessage::TakeSnapshot { response } => {
-                    info!("收到 UI 快照请求");
-                    commands.queue(move |world: &mut World| {
-                        let nodes = build_ui_snapshot(world);
-                        info!("UI 快照节点数: {}", nodes.len());
-                        let _ = response.send(nodes);
-                    });
+            _ => unreachable!("非输入阶段消息已暂存"),
+        }
+    }
+}
+
+// 交互阶段：在 UI 焦点系统之后覆盖 Interaction，避免被真实指针状态重置
+pub fn apply_interaction_messages(world: &mut World) {
+    let messages = std::mem::take(&mut world.resource_mut::<StagedTestMessages>().interaction);
+    // 暂存期间被取消的消息不再执行
+    for msg in messages.into_iter().filter(|msg| !msg.is_cancelled()) {
+        match msg {
+            TestMessage::Hover { x, y, response } => {
+                info!("收到测试悬停消息: ({}, {})", x, y);
+                cursor::move_to(world, Vec2::new(x, y));
+                set_button_interaction(world, Interaction::Hovered);
+                let _ = response.send(true);
+            }
+            TestMessage::Click { x, y, response } => {
+                info!("收到测试点击消息: ({}, {})", x, y);
+                cursor::press(world, Vec2::new(x, y));
+                set_button_interaction(world, Interaction::Pressed);
+                let _ = response.send(true);
+            }
+
+            // ---- 按 ID 操作元素 ----
+            TestMessage::ClickById { id, response } => {
+                info!("收到 ClickById: {}", id);
+                let result = find_entity_and_set_interaction(world, &id, Interaction::Pressed);
+                let _ = response.send(result);
+            }
+            TestMessage::HoverById { id, response } => {
+                info!("收到 HoverById: {}", id);
+                let result = find_entity_and_set_interaction(world, &id, Interaction::Hovered);
+                let _ = response.send(result);
+            }
+            TestMessage::ClickButtonByName {
+                button_name,
+                response,
+            } => {
+                info!("收到 ClickButtonByName: {}", button_name);
+                let result =
+                    find_entity_and_set_interaction(world, &button_name, Interaction::Pressed);
+                let _ = response.send(result);
+            }
+            TestMessage::FillText {
+                id,
+                value,
+                response,
+            } => {
+                info!("收到 FillText: {} = '{}'", id, value);
+                let Some(entity) = find_entity_by_test_id(world, &id) else {
+                    info!("FillText 失败: 未找到 id={}", id);
+                    let _ =
+                        response.send(Err(ActionError::NotFound(format!("未找到元素: {}", id))));
+                    continue;
+                };
+                if let Some(mut text) = world.get_mut::<Text>(entity) {
+                    *text = Text::new(&value);
+                    info!("FillText 成功: entity={:?}", entity);
+                    let _ = response.send(Ok(()));
+                } else {
+                    info!("FillText 失败: {} 没有 Text 组件", id);
+                    let _ = response.send(Err(ActionError::NotActionable(format!(
+                        "元素 {} 不是文本节点",
+                        id
+                    ))));
                 }
+            }
 
-                // ---- 按 ID 操作元素 ----
-                TestMessage::ClickById { id, response } => {
-                    info!("收到 ClickById: {}", id);
-                    commands.queue(move |world: &mut World| {
-                        let found =
-                            find_entity_and_set_interaction(world, &id, Interaction::Pressed);
-                        let _ = response.send(found);
+            // ---- 拖拽 ----
+            TestMessage::Drag {
+                from_id,
+                to_id,
+                response,
+            } => {
+                info!("收到 Drag: {} -> {}", from_id, to_id);
+                // 模拟：按下源元素，悬停目标元素
+                let result = find_entity_and_set_interaction(world, &from_id, Interaction::Pressed)
+                    .and_then(|()| {
+                        find_entity_and_set_interaction(world, &to_id, Interaction::Hovered)
                     });
+                let _ = response.send(result);
+            }
+
+            // ---- 调试覆盖层 ----
+            TestMessage::Highlight {
+                selector,
+                color,
+                label,
+                response,
+            } => {
+                info!("收到 Highlight: {}", selector);
+                let entities = find_entities_by_selector(world, &selector);
+                let color = Color::srgb(color[0], color[1], color[2]);
+                for &entity in &entities {
+                    highlight::spawn(world, entity, color, label.clone());
                 }
-                TestMessage::HoverById { id, response } => {
-                    info!("收到 HoverById: {}", id);
-                    commands.queue(move |world: &mut World| {
-                        let found =
-                            find_entity_and_set_interaction(world, &id, Interaction::Hovered);
-                        let _ = response.send(found);
-                    });
+                let _ = response.send(entities.len());
+            }
+            TestMessage::ClearHighlights { response } => {
+                let removed = highlight::clear(world);
+                info!("已清除 {} 个高亮", removed);
+                let _ = response.send(removed);
+            }
+            TestMessage::SetVirtualCursor { enabled, response } => {
+                info!("虚拟光标: {}", if enabled { "开启" } else { "关闭" });
+                cursor::set_enabled(world, enabled);
+                let _ = response.send(enabled);
+            }
+
+            // ---- 游戏自定义工具 ----
+            TestMessage::CustomTool {
+                name,
+                args,
+                response,
+            } => {
+                info!("收到自定义工具调用: {}", name);
+                let _ = response.send(custom_tools::run(world, &name, args));
+            }
+
+            _ => unreachable!("非交互阶段消息"),
+        }
+    }
+}
+
+// 查询阶段：在 Last 中执行，此时本帧 Update 与 PostUpdate（布局）均已完成
+pub fn apply_query_messages(world: &mut World) {
+    let messages = std::mem::take(&mut world.resource_mut::<StagedTestMessages>().query);
+    // 暂存期间被取消的消息不再执行
+    for msg in messages.into_iter().filter(|msg| !msg.is_cancelled()) {
+        match msg {
+            TestMessage::Screenshot { options, response } => {
+                info!("收到截图请求: {:?}", options);
+                let annotation = options.annotate.then(|| Annotation {
+                    marks: annotation_marks(world),
+                    font: default_font_data(world),
+                });
+                screenshot::capture_encoded(world, options, annotation, response);
+            }
+            TestMessage::ElementScreenshot {
+                selectors,
+                padding,
+                encoding,
+                response,
+            } => {
+                info!("收到元素截图请求: {:?}", selectors);
+                let mut regions = Vec::new();
+                let mut missing = Vec::new();
+                for selector in selectors {
+                    let matched: Vec<_> = find_entities_by_selector(world, &selector)
+                        .into_iter()
+                        .filter_map(|entity| {
+                            node_physical_rect(world, entity, padding).map(|rect| {
+                                screenshot::ElementRegion {
+                                    selector: selector.clone(),
+                                    uid: format!("bits:{}", entity.to_bits()),
+                                    rect,
+                                }
+                            })
+                        })
+                        .collect();
+                    if matched.is_empty() {
+                        missing.push(selector);
+                    }
+                    regions.extend(matched);
                 }
-                TestMessage::ClickButtonByName {
-                    button_name,
-                    response,
-                } => {
-                    info!("收到 ClickButtonByName: {}", button_name);
-                    commands.queue(move |world: &mut World| {
-                        let found = find_entity_and_set_interaction(
-                            world,
-                            &button_name,
-                            Interaction::Pressed,
-                        );
-                        let _ = response.send(found);
-                    });
+                if missing.is_empty() {
+                    screenshot::capture_elements(world, regions, encoding, response);
+                } else {
+                    let _ = response.send(Err(ScreenshotError::NotFound {
+                        message: format!("未找到元素: {}", missing.join(", ")),
+                        unmatched: missing.into_iter().map(Into::into).collect(),
+                    }));
                 }
-
-                // ---- 键盘 / 文本输入 ----
-                TestMessage::PressKey { key, response } => {
-                    info!("收到 PressKey: {}", key);
-                    let success = if let Some(key_code) = parse_key_code(&key) {
-                        keyboard_input.press(key_code);
-                        info!("PressKey 成功: {:?}", key_code);
-                        true
-                    } else {
-                        info!("未知按键名称: {}", key);
-                        false
+            }
+            TestMessage::ProbePixels { targets, response } => {
+                info!("收到像素探测请求: {:?}", targets);
+                let mut regions = Vec::new();
+                let mut missing = Vec::new();
+                for target in targets {
+                    let rect = match &target {
+                        ProbeTarget::Point { x, y } => {
+                            // 像素 (x, y) 覆盖 [x, x+1) × [y, y+1)
+                            let min = Vec2::new(x.floor(), y.floor());
+                            Some(Rect::from_corners(min, min + Vec2::ONE))
+                        }
+                        ProbeTarget::Rect {
+                            x,
+                            y,
+                            width,
+                            height,
+                        } => Some(Rect::new(*x, *y, x + width, y + height)),
+                        ProbeTarget::Element(selector) => {
+                            find_entities_by_selector(world, selector)
+                                .into_iter()
+                                .find_map(|entity| node_physical_rect(world, entity, 0.0))
+                        }
                     };
-                    let _ = response.send(success);
+                    match rect {
+                        Some(rect) => regions.push((target, rect)),
+                        None => missing.push(target),
+                    }
                 }
-                TestMessage::FillText {
-                    id,
-                    value,
-                    response,
-                } => {
-                    info!("收到 FillText: {} = '{}'", id, value);
-                    let value_clone = value.clone();
-                    commands.queue(move |world: &mut World| {
-                        if let Some(entity) = find_entity_by_test_id(world, &id) {
-                            if let Some(mut text) = world.get_mut::<Text>(entity) {
-                                *text = Text::new(&value_clone);
-                                info!("FillText 成功: entity={:?}", entity);
-                                let _ = response.send(true);
-                                return;
-                            }
-                        }
-                        info!("FillText 失败: 未找到 id={}", id);
-                        let _ = response.send(false);
-                    });
+                if missing.is_empty() {
+                    screenshot::capture_probe(world, regions, response);
+                } else {
+                    let names: Vec<String> = missing.iter().map(ToString::to_string).collect();
+                    let _ = response.send(Err(ScreenshotError::NotFound {
+                        message: format!("未找到{}", names.join("、")),
+                        unmatched: missing.iter().map(|t| serde_json::json!(t)).collect(),
+                    }));
                 }
+            }
+            TestMessage::StartRecording { options, response } => {
+                let _ = response.send(recording::start(world, options));
+            }
+            TestMessage::StopRecording { path, response } => {
+                info!("收到停止录制请求: {:?}", path);
+                recording::stop(world, path, response);
+            }
+            TestMessage::EnvironmentInfo { response } => {
+                let _ = response.send(environment::collect(world));
+            }
+            TestMessage::QueryComponents { response } => {
+                info!("收到组件查询消息");
+                let ball_count = world.query::<&Ball>().iter(world).count();
+                let button_count = world.query::<&GameButton>().iter(world).count();
+
+                let mut counts = std::collections::HashMap::new();
+                counts.insert("Ball".to_string(), ball_count);
+                counts.insert("Button".to_string(), button_count);
+
+                info!(
+                    "COMPONENT_COUNTS: Ball={}, Button={}",
+                    ball_count, button_count
+                );
+                let _ = response.send(counts);
+            }
 
-                // ---- 拖拽 ----
-                TestMessage::Drag {
-                    from_id,
-                    to_id,
-                    response,
-                } => {
-                    info!("收到 Drag: {} -> {}", from_id, to_id);
-                    commands.queue(move |world: &mut World| {
-                        // 模拟：按下源元素，悬停目标元素
-                        let success =
-                            find_entity_and_set_interaction(world, &from_id, Interaction::Pressed);
-                        find_entity_and_set_interaction(world, &to_id, Interaction::Hovered);
-                        let _ = response.send(success);
-                    });
-                }
+            // ---- CDP 风格：UI 快照 ----
+            TestMessage::TakeSnapshot { response } => {
+                info!("收到 UI 快照请求");
+                let nodes = build_ui_snapshot(world);
+                info!("UI 快照节点数: {}", nodes.len());
+                let _ = response.send(nodes);
+            }
 
-                // ---- 日志 / 脚本 ----
-                TestMessage::GetLogs {
-                    lines,
-                    log_file,
-                    response,
-                } => {
-                    info!("收到 GetLogs: lines={}", lines);
-                    std::thread::spawn(move || {
-                        let file_path = log_file.unwrap_or_else(|| {
-                            std::env::var("TEST_LOG_FILE")
-                                .unwrap_or_else(|_| "logs/game.log".to_string())
-                        });
-                        let entries = read_log_file(&file_path, lines);
-                        let _ = response.send(entries);
-                    });
-                }
-                TestMessage::EvaluateScript { script, response } => {
-                    info!("收到 EvaluateScript");
-                    std::thread::spawn(move || {
-                        // 若设置了 JS_EVALUATOR_URL，向 Tauri IPC 端点 POST 脚本
-                        if let Ok(url) = std::env::var("JS_EVALUATOR_URL") {
-                            // 简单 HTTP POST（需要游戏侧有对应端点）
-                            let result = std::process::Command::new("curl")
-                                .args([
-                                    "-s",
-                                    "-X",
-                                    "POST",
-                                    "-H",
-                                    "Content-Type: application/json",
-                                    "-d",
-                                    &format!(
-                                        "{{\"script\":{}}}",
-                                        serde_json::to_string(&script).unwrap_or_default()
-                                    ),
-                                    &url,
-                                ])
-                                .output()
-                                .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-                                .unwrap_or_else(|e| format!("{{\"error\":\"curl 失败: {}\"}}", e));
-                            let _ = response.send(result);
-                        } else {
-                            let _ = response.send(
-                                "{\"error\":\"未配置 JS_EVALUATOR_URL 环境变量，Tauri 集成需要设置此变量\"}".to_string()
-                            );
-                        }
-                    });
-                }
+            // ---- 生命周期 ----
+            TestMessage::Exit { response } => {
+                info!("收到退出请求");
+                world.write_message(AppExit::Success);
+                let _ = response.send(());
             }
+
+            _ => unreachable!("非查询阶段消息"),
         }
     }
 }
 
+// 在 Last 中检查帧同步屏障，满足等待帧数后返回当前帧号
+pub fn resolve_settles(frame_count: Res<FrameCount>, mut pending_settles: ResMut<PendingSettles>) {
+    let current = frame_count.0;
+    let (ready, waiting): (Vec<_>, Vec<_>) = pending_settles
+        .0
+        .drain(..)
+        // 调用已取消的屏障直接丢弃
+        .filter(|(_, _, response)| !response.is_closed())
+        .partition(|(start, wait, _)| current.wrapping_sub(*start) >= *wait);
+    pending_settles.0 = waiting;
+    for (_, _, response) in ready {
+        let _ = response.send(current);
+    }
+}
+
 // ============================================================
 // 辅助函数
 // ============================================================
@@ -372,24 +550,147 @@ fn find_entity_by_test_id(world: &mut World, id: &str) -> Option<Entity> {
     None
 }
 
-/// 找到实体并设置 Interaction 组件，返回是否成功
-fn find_entity_and_set_interaction(world: &mut World, id: &str, interaction: Interaction) -> bool {
-    if let Some(entity) = find_entity_by_test_id(world, id) {
-        if let Some(mut inter) = world.get_mut::<Interaction>(entity) {
-            *inter = interaction;
-            info!("设置 Interaction {:?} for entity {:?}", interaction, entity);
-            return true;
+/// 按选择器查找所有匹配实体
+///
+/// 支持 `testId=…`、`name=…`、`text=…`、`type=button|text|container`；
+/// 其余按 test_id / Name / "bits:{n}" 查找单个实体
+fn find_entities_by_selector(world: &mut World, selector: &str) -> Vec<Entity> {
+    let Some((key, value)) = selector.split_once('=') else {
+        return find_entity_by_test_id(world, selector)
+            .into_iter()
+            .collect();
+    };
+    let mut query = world.query_filtered::<(
+        Entity,
+        Option<&TestId>,
+        Option<&Name>,
+        Option<&Text>,
+        Has<Button>,
+    ), (With<Node>, Without<TestOverlay>)>();
+    query
+        .iter(world)
+        .filter(|(_, test_id, name, text, is_button)| match key {
+            "testId" => test_id.is_some_and(|t| t.0 == value),
+            "name" => name.is_some_and(|n| n.as_str() == value),
+            "text" => text.is_some_and(|t| t.0 == value),
+            "type" => {
+                let node_type = if *is_button {
+                    "button"
+                } else if text.is_some() {
+                    "text"
+                } else {
+                    "container"
+                };
+                node_type == value
+            }
+            _ => false,
+        })
+        .map(|(entity, ..)| entity)
+        .collect()
+}
+
+/// 节点在渲染目标上的矩形（物理像素），四周加上 padding（逻辑像素）
+///
+/// ComputedNode 的尺寸与 UiGlobalTransform 的中心点都已是物理像素，
+/// padding 通过 inverse_scale_factor 换算为物理像素
+fn node_physical_rect(world: &World, entity: Entity, padding: f32) -> Option<Rect> {
+    let node = world.get::<ComputedNode>(entity)?;
+    let transform = world.get::<UiGlobalTransform>(entity)?;
+    if node.size().cmple(Vec2::ZERO).any() {
+        return None;
+    }
+    let padding = padding / node.inverse_scale_factor();
+    Some(Rect::from_center_size(transform.translation, node.size()).inflate(padding))
+}
+
+/// 节点中心的逻辑像素坐标（与 hover / click 的坐标一致）
+fn node_logical_center(world: &World, entity: Entity) -> Option<Vec2> {
+    let scale = world.get::<ComputedNode>(entity)?.inverse_scale_factor();
+    Some(node_physical_rect(world, entity, 0.0)?.center() * scale)
+}
+
+/// 设置所有 GameButton 的 Interaction（坐标类 hover/click 使用）
+fn set_button_interaction(world: &mut World, interaction: Interaction) {
+    let mut query = world.query_filtered::<&mut Interaction, With<GameButton>>();
+    for mut inter in query.iter_mut(world) {
+        *inter = interaction;
+    }
+}
+
+/// 找到实体并设置 Interaction 组件；未找到或不可交互时返回对应错误
+fn find_entity_and_set_interaction(
+    world: &mut World,
+    id: &str,
+    interaction: Interaction,
+) -> Result<(), ActionError> {
+    let Some(entity) = find_entity_by_test_id(world, id) else {
+        info!("未找到元素: {}", id);
+        return Err(ActionError::NotFound(format!("未找到元素: {}", id)));
+    };
+    // 虚拟光标指向元素中心
+    if let Some(center) = node_logical_center(world, entity) {
+        match interaction {
+            Interaction::Pressed => cursor::press(world, center),
+            _ => cursor::move_to(world, center),
         }
     }
-    info!("未找到可交互元素: {}", id);
-    false
+    let Some(mut inter) = world.get_mut::<Interaction>(entity) else {
+        info!("元素不可交互: {}", id);
+        return Err(ActionError::NotActionable(format!(
+            "元素 {} 没有 Interaction 组件，无法交互",
+            id
+        )));
+    };
+    *inter = interaction;
+    info!("设置 Interaction {:?} for entity {:?}", interaction, entity);
+    Ok(())
+}
+
+/// 标注截图的编号框：可见且有尺寸的可交互节点，按从上到下、从左到右编号
+fn annotation_marks(world: &mut World) -> Vec<AnnotationMark> {
+    let mut nodes: Vec<UINodeData> = build_ui_snapshot(world)
+        .into_iter()
+        .filter(|n| n.visible && n.interactive && n.rect_px.is_some())
+        .collect();
+    let rect = |n: &UINodeData| n.rect_px.unwrap_or_default();
+    nodes.sort_by(|a, b| {
+        let (a, b) = (rect(a).min, rect(b).min);
+        a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+    });
+    nodes
+        .into_iter()
+        .zip(1..)
+        .map(|(n, number)| {
+            let rect = rect(&n);
+            AnnotationMark {
+                number,
+                uid: n.uid,
+                test_id: n.test_id,
+                name: n.name,
+                text: n.text,
+                x: rect.min.x,
+                y: rect.min.y,
+                width: rect.width(),
+                height: rect.height(),
+            }
+        })
+        .collect()
+}
+
+/// font_manager 设置的默认字体数据（用于在截图上绘制标签）
+fn default_font_data(world: &World) -> Option<std::sync::Arc<Vec<u8>>> {
+    let handle = TextFont::default().font;
+    world
+        .get_resource::<Assets<Font>>()?
+        .get(&handle)
+        .map(|font| font.data.clone())
 }
 
 /// 构建 UI 节点快照（遍历所有带 Node 组件的实体）
 fn build_ui_snapshot(world: &mut World) -> Vec<UINodeData> {
     // 收集所有 UI 实体
     let entities: Vec<Entity> = world
-        .query_filtered::<Entity, With<Node>>()
+        .query_filtered::<Entity, (With<Node>, Without<TestOverlay>)>()
         .iter(world)
         .collect();
 
@@ -408,6 +709,7 @@ fn build_ui_snapshot(world: &mut World) -> Vec<UINodeData> {
         let text = world.get::<Text>(entity).map(|t| t.0.clone());
 
         let is_button = world.get::<Button>(entity).is_some();
+        let interactive = is_button || world.get::<Interaction>(entity).is_some();
         let node_type = if is_button {
             "button"
         } else if text.is_some() {
@@ -423,23 +725,21 @@ fn build_ui_snapshot(world: &mut World) -> Vec<UINodeData> {
             .map(|v| *v != Visibility::Hidden)
             .unwrap_or(true);
 
-        // ComputedNode 提供计算后的像素尺寸
+        // ComputedNode 的尺寸是物理像素，换算为逻辑像素，与 x/y 一致
         let (width, height) = world
             .get::<ComputedNode>(entity)
             .map(|cn| {
-                let s = cn.size();
+                let s = cn.size() * cn.inverse_scale_factor();
                 (s.x, s.y)
             })
             .unwrap_or((0.0, 0.0));
 
-        // GlobalTransform.translation 给出世界空间位置
-        let (x, y) = world
-            .get::<GlobalTransform>(entity)
-            .map(|gt| {
-                let t = gt.translation();
-                (t.x, t.y)
-            })
+        // x/y 为节点中心的逻辑像素坐标，可直接用于 click / hover；
+        // 截图标注与 mask 使用物理像素的 rect_px
+        let (x, y) = node_logical_center(world, entity)
+            .map(|c| (c.x, c.y))
             .unwrap_or((0.0, 0.0));
+        let rect_px = node_physical_rect(world, entity, 0.0);
 
         let parent_uid = world
             .get::<ChildOf>(entity)
@@ -452,10 +752,12 @@ fn build_ui_snapshot(world: &mut World) -> Vec<UINodeData> {
             text,
             test_id,
             visible,
+            interactive,
             x,
             y,
             width,
             height,
+            rect_px,
             parent_uid,
         });
     }
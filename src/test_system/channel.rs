@@ -1,10 +1,15 @@
 use crossbeam_channel::{Receiver, Sender};
+use serde::Serialize;
+use std::sync::atomic::AtomicU32;
 use std::sync::OnceLock;
 use tokio::sync::oneshot;
 
 // 全局消息通道，供游戏主循环接收命令
 pub static TEST_COMMAND_CHANNEL: OnceLock<TestChannel> = OnceLock::new();
 
+// 最近一次接收测试消息时的 FrameCount，不需要等待 settle 的调用直接用它作为帧号
+pub static CURRENT_FRAME: AtomicU32 = AtomicU32::new(0);
+
 // 测试命令通道
 #[allow(dead_code)]
 pub struct TestChannel {
@@ -15,6 +20,9 @@ pub struct TestChannel {
 // ---- 数据结构（供 channel 传输） ----
 
 /// UI 快照节点数据（扁平结构，客户端可自行构建树）
+///
+/// x / y / width / height 均为逻辑像素（与 click / hover 坐标一致），
+/// 只有 rect_px 是物理像素（与截图像素一致）
 #[derive(Clone, Debug, Default)]
 pub struct UINodeData {
     /// 唯一标识符，格式 "bits:{entity_bits}"
@@ -29,18 +37,407 @@ pub struct UINodeData {
     pub test_id: Option<String>,
     /// 是否可见
     pub visible: bool,
-    /// 屏幕像素坐标 X
+    /// 是否可交互（Button 或带 Interaction 组件）
+    pub interactive: bool,
+    /// 节点中心屏幕坐标 X（逻辑像素，与 hover / click 的坐标一致）
     pub x: f32,
-    /// 屏幕像素坐标 Y
+    /// 节点中心屏幕坐标 Y（逻辑像素）
     pub y: f32,
-    /// 计算后宽度（像素）
+    /// 计算后宽度（逻辑像素）
     pub width: f32,
-    /// 计算后高度（像素）
+    /// 计算后高度（逻辑像素）
     pub height: f32,
+    /// 节点在渲染目标上的矩形（物理像素，与截图像素一致）；未完成布局时为 None
+    pub rect_px: Option<bevy::math::Rect>,
     /// 父节点 uid（根节点为 None）
     pub parent_uid: Option<String>,
 }
 
+/// 响应时机：决定命令在哪一帧之后返回，提供 read-after-write 一致性
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Settle {
+    /// 命令响应后立即返回，不等待帧（默认）
+    #[default]
+    Immediate,
+    /// 本帧 Update 结束后响应（此时 Commands 已应用）
+    Update,
+    /// 本帧渲染完成后响应
+    Render,
+    /// 本帧之后再等待 N 帧响应
+    Frames(u32),
+}
+
+impl Settle {
+    /// 从登记帧起需要经过的帧数（以 Last 中递增的 FrameCount 计）
+    pub fn frames_to_wait(self) -> u32 {
+        match self {
+            Settle::Immediate => 0,
+            Settle::Update => 1,
+            // 流水线渲染下，第 N 帧在第 N+1 帧主循环期间渲染
+            Settle::Render => 2,
+            Settle::Frames(n) => n.saturating_add(1),
+        }
+    }
+}
+
+/// 测试消息的执行阶段（见 TestSystems）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestStage {
+    /// PreUpdate：输入采集（InputSystems）之后、拾取与 UI 焦点之前，注入键盘等原始输入
+    Input,
+    /// PreUpdate：UI 焦点（UiSystems::Focus）之后、Update 之前，覆盖 Interaction 或修改 UI
+    Interaction,
+    /// Last：本帧 Update / PostUpdate 完成后查询 World
+    Query,
+}
+
+impl TestStage {
+    /// 阶段名（用于工具元数据）
+    pub fn name(self) -> &'static str {
+        match self {
+            TestStage::Input => "input",
+            TestStage::Interaction => "interaction",
+            TestStage::Query => "query",
+        }
+    }
+
+    /// 阶段所在调度及排序说明（用于工具元数据）
+    pub fn schedule(self) -> &'static str {
+        match self {
+            TestStage::Input => {
+                "PreUpdate: after InputSystems, before PickingSystems / UiSystems::Focus"
+            }
+            TestStage::Interaction => "PreUpdate: after UiSystems::Focus, before Update",
+            TestStage::Query => "Last: after Update and PostUpdate (layout is up to date)",
+        }
+    }
+}
+
+/// 截图编码格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageEncoding {
+    #[default]
+    Png,
+    Jpeg {
+        /// 1-100
+        quality: u8,
+    },
+    /// 无损 WebP
+    WebP,
+}
+
+impl ImageEncoding {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "image/png",
+            ImageEncoding::Jpeg { .. } => "image/jpeg",
+            ImageEncoding::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "png",
+            ImageEncoding::Jpeg { .. } => "jpg",
+            ImageEncoding::WebP => "webp",
+        }
+    }
+}
+
+/// 截图选项
+#[derive(Clone, Debug, Default)]
+pub struct ScreenshotOptions {
+    /// 保存路径（None 时只在内存中返回）
+    pub path: Option<String>,
+    pub encoding: ImageEncoding,
+    /// 缩放比例（0, 1]
+    pub scale: Option<f32>,
+    /// 最大宽度（像素），超过时等比缩小
+    pub max_width: Option<u32>,
+    /// 等待画面稳定后再返回（None 时截取下一帧）
+    pub stable: Option<StableOptions>,
+    /// 为可见的可交互节点绘制编号框（set-of-marks）
+    pub annotate: bool,
+}
+
+/// 稳定截图：连续 frames 帧在容差内一致，或超时后才返回
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StableOptions {
+    /// 需要连续一致的帧数（含最后一帧）
+    pub frames: u32,
+    /// 单通道允许的差值
+    pub tolerance: u8,
+    /// 超时（毫秒），超时后返回最后一帧
+    pub timeout_ms: u64,
+}
+
+impl Default for StableOptions {
+    fn default() -> Self {
+        Self {
+            frames: 3,
+            tolerance: 0,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// 稳定截图的结果
+#[derive(Clone, Copy, Debug)]
+pub struct Stability {
+    /// 是否在超时前达到稳定
+    pub stable: bool,
+    /// 共比较的帧数
+    pub frames_captured: u32,
+}
+
+/// 编码后的截图
+#[derive(Clone, Debug)]
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// 稳定截图时的等待结果
+    pub stability: Option<Stability>,
+    /// 标注截图的图例（编号 → 节点），未标注时为空
+    pub marks: Vec<AnnotationMark>,
+}
+
+/// 标注截图中的一个编号框
+#[derive(Clone, Debug)]
+pub struct AnnotationMark {
+    /// 从 1 开始的编号
+    pub number: u32,
+    pub uid: String,
+    pub test_id: Option<String>,
+    pub name: String,
+    pub text: Option<String>,
+    /// 节点矩形（整帧物理像素）
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// 元素截图结果：按节点矩形裁剪后的图像
+#[derive(Clone, Debug)]
+pub struct ElementImage {
+    /// 匹配该元素的选择器
+    pub selector: String,
+    pub uid: String,
+    /// 裁剪区域（物理像素，含 padding，已限制在画面内）
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub image: EncodedImage,
+}
+
+/// 像素探测目标（物理像素，与截图像素一致）；序列化为与 probe_pixels 参数相同的形状
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ProbeTarget {
+    Point {
+        x: f32,
+        y: f32,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// 元素选择器，取第一个匹配节点的矩形
+    Element(String),
+}
+
+impl std::fmt::Display for ProbeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeTarget::Point { x, y } => write!(f, "点 ({}, {})", x, y),
+            ProbeTarget::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "矩形 ({}, {}, {}×{})", x, y, width, height),
+            ProbeTarget::Element(selector) => write!(f, "元素 \"{}\"", selector),
+        }
+    }
+}
+
+/// 区域内的 sRGB 颜色统计（8 位通道）
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ColorStats {
+    pub avg: [f32; 3],
+    pub min: [u8; 3],
+    pub max: [u8; 3],
+    /// 逐通道中位数，不受按钮文字等少量异色像素影响
+    pub median: [u8; 3],
+    pub pixels: u32,
+}
+
+/// 像素探测结果
+#[derive(Clone, Debug)]
+pub struct ProbeResult {
+    pub target: ProbeTarget,
+    /// 实际采样区域（已限制在画面内）
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub stats: ColorStats,
+}
+
+/// 录制编码格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// 动画 PNG（无损）
+    #[default]
+    Apng,
+    Gif,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Apng => "png",
+            RecordingFormat::Gif => "gif",
+        }
+    }
+}
+
+/// 录制选项
+#[derive(Clone, Debug)]
+pub struct RecordingOptions {
+    /// 每 N 帧截取一次
+    pub every_n_frames: u32,
+    pub format: RecordingFormat,
+    /// 缩放比例（0, 1]
+    pub scale: Option<f32>,
+    /// 最大宽度（像素）
+    pub max_width: Option<u32>,
+    /// 内存中最多保留的帧数，超出时丢弃最早的帧
+    pub max_frames: usize,
+}
+
+/// 录制结果
+#[derive(Clone, Debug, Default)]
+pub struct RecordingSummary {
+    /// 写入的文件（丢弃录制时为 None）
+    pub path: Option<String>,
+    pub frames: u32,
+    /// 因超过 max_frames 被丢弃的最早帧数
+    pub dropped: u32,
+    pub width: u32,
+    pub height: u32,
+    pub duration_ms: u64,
+    pub bytes: usize,
+}
+
+/// 截图失败原因（结构化，供 MCP 客户端按 code 判断）
+#[derive(Clone, Debug)]
+pub enum ScreenshotError {
+    /// 捕获到的纹理格式无法转换为图像
+    Convert(String),
+    /// 图像编码失败
+    Encode(String),
+    /// 写入文件失败
+    Write(String),
+    /// 选择器未匹配到可见节点，或区域不在画面内；unmatched 为对应的原始目标
+    NotFound {
+        message: String,
+        unmatched: Vec<serde_json::Value>,
+    },
+    /// 超时前未捕获到任何帧
+    Timeout(String),
+    /// 当前没有进行中的录制，或录制中没有帧
+    NotRecording(String),
+}
+
+impl ScreenshotError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScreenshotError::Convert(_) => "convert_failed",
+            ScreenshotError::Encode(_) => "encode_failed",
+            ScreenshotError::Write(_) => "write_failed",
+            ScreenshotError::NotFound { .. } => "not_found",
+            ScreenshotError::Timeout(_) => "timeout",
+            ScreenshotError::NotRecording(_) => "not_recording",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ScreenshotError::Convert(m)
+            | ScreenshotError::Encode(m)
+            | ScreenshotError::Write(m)
+            | ScreenshotError::NotFound { message: m, .. }
+            | ScreenshotError::Timeout(m)
+            | ScreenshotError::NotRecording(m) => m,
+        }
+    }
+
+    /// 附加在 MCP 错误 details 中的信息
+    pub fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            ScreenshotError::NotFound { unmatched, .. } if !unmatched.is_empty() => {
+                Some(serde_json::json!({ "unmatched": unmatched }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// 交互命令（按 ID 点击、填充文本、拖拽、按键）的失败原因
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActionError {
+    /// 选择器未匹配到节点
+    NotFound(String),
+    /// 节点存在但不支持该操作（没有 Interaction / Text 组件）
+    NotActionable(String),
+    /// 参数取值无效，如未知按键名称
+    InvalidArgument(String),
+}
+
+impl ActionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ActionError::NotFound(_) => "not_found",
+            ActionError::NotActionable(_) => "not_actionable",
+            ActionError::InvalidArgument(_) => "invalid_argument",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ActionError::NotFound(m)
+            | ActionError::NotActionable(m)
+            | ActionError::InvalidArgument(m) => m,
+        }
+    }
+}
+
+/// 渲染环境信息（见 environment.rs）
+#[derive(Clone, Debug, Default)]
+pub struct EnvironmentInfo {
+    /// wgpu 适配器名，如 "llvmpipe (LLVM 15.0.7, 256 bits)"
+    pub adapter: String,
+    /// "vulkan" | "gl" | "metal" | "dx12" ...
+    pub backend: String,
+    pub device_type: String,
+    pub driver: String,
+    pub driver_info: String,
+    /// 渲染目标的纹理格式（窗口模式下首帧渲染前未知）
+    pub surface_format: Option<String>,
+    pub scale_factor: f32,
+    pub headless: bool,
+    /// font_manager 实际加载的字体路径（None 表示 Bevy 内置字体）
+    pub font: Option<String>,
+    /// 可用作目录名的环境指纹，如 "gl-llvmpipe-1x"
+    pub fingerprint: String,
+}
+
 /// 日志条目数据
 #[derive(Clone, Debug, Default)]
 pub struct LogEntryData {
@@ -65,13 +462,41 @@ pub enum TestMessage {
         y: f32,
         response: oneshot::Sender<bool>,
     },
+    /// 截图：图像到达内存并缩放、编码（指定 path 时写入文件）后返回
     Screenshot {
-        path: String,
-        response: oneshot::Sender<bool>,
+        options: ScreenshotOptions,
+        response: oneshot::Sender<Result<EncodedImage, ScreenshotError>>,
+    },
+    /// 元素截图：一次渲染中按多个选择器裁剪节点区域
+    ElementScreenshot {
+        selectors: Vec<String>,
+        /// 四周留白（逻辑像素）
+        padding: f32,
+        encoding: ImageEncoding,
+        response: oneshot::Sender<Result<Vec<ElementImage>, ScreenshotError>>,
     },
     QueryComponents {
         response: oneshot::Sender<std::collections::HashMap<String, usize>>,
     },
+    /// 渲染环境信息
+    EnvironmentInfo {
+        response: oneshot::Sender<EnvironmentInfo>,
+    },
+    /// 像素探测：截取一帧，返回各点颜色或各区域的颜色统计
+    ProbePixels {
+        targets: Vec<ProbeTarget>,
+        response: oneshot::Sender<Result<Vec<ProbeResult>, ScreenshotError>>,
+    },
+    /// 开始录制：每 N 帧截取一次到内存（已在录制时返回错误）
+    StartRecording {
+        options: RecordingOptions,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    /// 停止录制并编码写入 path（None 时丢弃录制；不含扩展名时按录制格式补全）
+    StopRecording {
+        path: Option<String>,
+        response: oneshot::Sender<Result<RecordingSummary, ScreenshotError>>,
+    },
 
     // ---- CDP 风格 UI 快照 ----
     /// 获取 UI 节点树快照（类似 CDP take_snapshot / a11y 树）
@@ -83,30 +508,55 @@ pub enum TestMessage {
     /// 按 test_id / Name / "bits:{n}" 点击元素
     ClickById {
         id: String,
-        response: oneshot::Sender<bool>,
+        response: oneshot::Sender<Result<(), ActionError>>,
     },
     /// 按 test_id / Name / "bits:{n}" 悬停元素
     HoverById {
         id: String,
-        response: oneshot::Sender<bool>,
+        response: oneshot::Sender<Result<(), ActionError>>,
     },
     /// 按 test_id / Name / "bits:{n}" 点击按钮（名称匹配）
     ClickButtonByName {
         button_name: String,
+        response: oneshot::Sender<Result<(), ActionError>>,
+    },
+
+    // ---- 调试覆盖层 ----
+    /// 为选择器匹配的所有节点绘制高亮边框（直到 ClearHighlights），返回匹配数量
+    Highlight {
+        selector: String,
+        /// sRGB 颜色（0-1）
+        color: [f32; 3],
+        label: Option<String>,
+        response: oneshot::Sender<usize>,
+    },
+    /// 移除所有高亮，返回移除数量
+    ClearHighlights { response: oneshot::Sender<usize> },
+    /// 开启或关闭虚拟光标
+    SetVirtualCursor {
+        enabled: bool,
         response: oneshot::Sender<bool>,
     },
 
+    // ---- 游戏自定义工具 ----
+    /// 调用游戏通过 register_test_tool 注册的工具（参数已通过 inputSchema 校验）
+    CustomTool {
+        name: String,
+        args: serde_json::Value,
+        response: oneshot::Sender<Result<serde_json::Value, String>>,
+    },
+
     // ---- 键盘 / 文本输入 ----
     /// 模拟按键（支持 "Space" / "Enter" / "Escape" / "ArrowUp" 等）
     PressKey {
         key: String,
-        response: oneshot::Sender<bool>,
+        response: oneshot::Sender<Result<(), ActionError>>,
     },
     /// 向元素填充文本（先清空再写入）
     FillText {
         id: String,
         value: String,
-        response: oneshot::Sender<bool>,
+        response: oneshot::Sender<Result<(), ActionError>>,
     },
 
     // ---- 拖拽 ----
@@ -114,7 +564,7 @@ pub enum TestMessage {
     Drag {
         from_id: String,
         to_id: String,
-        response: oneshot::Sender<bool>,
+        response: oneshot::Sender<Result<(), ActionError>>,
     },
 
     // ---- 日志 / 脚本 ----
@@ -129,4 +579,77 @@ pub enum TestMessage {
         script: String,
         response: oneshot::Sender<String>,
     },
+
+    // ---- 帧同步 ----
+    /// 帧同步屏障：满足 settle 条件后返回当时的帧号
+    Settle {
+        settle: Settle,
+        response: oneshot::Sender<u32>,
+    },
+
+    // ---- 生命周期 ----
+    /// 请求游戏正常退出（写入 AppExit），stdio 传输在 stdin 关闭后发送
+    Exit { response: oneshot::Sender<()> },
+}
+
+impl TestMessage {
+    /// 消息所属的执行阶段
+    pub fn stage(&self) -> TestStage {
+        match self {
+            TestMessage::PressKey { .. }
+            | TestMessage::GetLogs { .. }
+            | TestMessage::EvaluateScript { .. }
+            | TestMessage::Settle { .. } => TestStage::Input,
+            TestMessage::Hover { .. }
+            | TestMessage::Click { .. }
+            | TestMessage::ClickById { .. }
+            | TestMessage::HoverById { .. }
+            | TestMessage::ClickButtonByName { .. }
+            | TestMessage::FillText { .. }
+            | TestMessage::Drag { .. }
+            | TestMessage::Highlight { .. }
+            | TestMessage::ClearHighlights { .. }
+            | TestMessage::SetVirtualCursor { .. }
+            | TestMessage::CustomTool { .. } => TestStage::Interaction,
+            TestMessage::Screenshot { .. }
+            | TestMessage::ElementScreenshot { .. }
+            | TestMessage::QueryComponents { .. }
+            | TestMessage::EnvironmentInfo { .. }
+            | TestMessage::ProbePixels { .. }
+            | TestMessage::StartRecording { .. }
+            | TestMessage::StopRecording { .. }
+            | TestMessage::TakeSnapshot { .. }
+            | TestMessage::Exit { .. } => TestStage::Query,
+        }
+    }
+
+    /// 调用方已放弃等待（MCP 调用被取消或超时）时响应通道已关闭，不必再执行
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            TestMessage::Hover { response, .. } => response.is_closed(),
+            TestMessage::Click { response, .. } => response.is_closed(),
+            TestMessage::Screenshot { response, .. } => response.is_closed(),
+            TestMessage::ElementScreenshot { response, .. } => response.is_closed(),
+            TestMessage::QueryComponents { response, .. } => response.is_closed(),
+            TestMessage::EnvironmentInfo { response, .. } => response.is_closed(),
+            TestMessage::ProbePixels { response, .. } => response.is_closed(),
+            TestMessage::StartRecording { response, .. } => response.is_closed(),
+            TestMessage::StopRecording { response, .. } => response.is_closed(),
+            TestMessage::TakeSnapshot { response, .. } => response.is_closed(),
+            TestMessage::ClickById { response, .. } => response.is_closed(),
+            TestMessage::HoverById { response, .. } => response.is_closed(),
+            TestMessage::ClickButtonByName { response, .. } => response.is_closed(),
+            TestMessage::Highlight { response, .. } => response.is_closed(),
+            TestMessage::ClearHighlights { response, .. } => response.is_closed(),
+            TestMessage::SetVirtualCursor { response, .. } => response.is_closed(),
+            TestMessage::CustomTool { response, .. } => response.is_closed(),
+            TestMessage::PressKey { response, .. } => response.is_closed(),
+            TestMessage::FillText { response, .. } => response.is_closed(),
+            TestMessage::Drag { response, .. } => response.is_closed(),
+            TestMessage::GetLogs { response, .. } => response.is_closed(),
+            TestMessage::EvaluateScript { response, .. } => response.is_closed(),
+            TestMessage::Settle { response, .. } => response.is_closed(),
+            TestMessage::Exit { response } => response.is_closed(),
+        }
+    }
 }
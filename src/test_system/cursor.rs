@@ -0,0 +1,155 @@
+//! 虚拟光标：测试输入是合成的，屏幕上看不到指针；开启后在最近一次 hover / click / drag
+//! 的位置绘制指针，点击时播放按下波纹，截图与录制中即可看出交互顺序
+//!
+//! 以 --virtual-cursor 启动游戏或调用 virtual_cursor 工具开启
+
+use bevy::picking::Pickable;
+use bevy::prelude::*;
+
+use crate::test_system::highlight::TestOverlay;
+
+/// 指针直径（逻辑像素）
+const POINTER_SIZE: f32 = 16.0;
+/// 指针边框粗细
+const POINTER_BORDER: f32 = 2.0;
+/// 波纹起始与结束直径
+const RIPPLE_FROM: f32 = 12.0;
+const RIPPLE_TO: f32 = 48.0;
+/// 波纹持续时间（秒）
+const RIPPLE_SECONDS: f32 = 0.4;
+/// 波纹颜色
+const RIPPLE_COLOR: Color = Color::srgb(1.0, 0.3, 0.2);
+
+/// 虚拟光标状态：是否开启与最近一次指向的位置（逻辑像素）
+#[derive(Resource)]
+pub struct VirtualCursor {
+    pub enabled: bool,
+    position: Option<Vec2>,
+}
+
+impl Default for VirtualCursor {
+    fn default() -> Self {
+        Self {
+            enabled: std::env::args().any(|arg| arg == "--virtual-cursor"),
+            position: None,
+        }
+    }
+}
+
+/// 指针节点
+#[derive(Component)]
+pub struct CursorPointer;
+
+/// 按下波纹，elapsed 为已播放的秒数
+#[derive(Component)]
+pub struct Ripple {
+    center: Vec2,
+    elapsed: f32,
+}
+
+/// 开启或关闭虚拟光标；关闭时移除指针与波纹
+pub fn set_enabled(world: &mut World, enabled: bool) {
+    world.resource_mut::<VirtualCursor>().enabled = enabled;
+    if enabled {
+        return;
+    }
+    let overlays: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<CursorPointer>, With<Ripple>)>>()
+        .iter(world)
+        .collect();
+    for overlay in overlays {
+        world.entity_mut(overlay).despawn();
+    }
+}
+
+/// 指针移动到 position（hover）
+pub fn move_to(world: &mut World, position: Vec2) {
+    world.resource_mut::<VirtualCursor>().position = Some(position);
+}
+
+/// 指针移动到 position 并播放按下波纹（click / drag 起点）
+pub fn press(world: &mut World, position: Vec2) {
+    move_to(world, position);
+    if !world.resource::<VirtualCursor>().enabled {
+        return;
+    }
+    let mut node = Node {
+        position_type: PositionType::Absolute,
+        border: UiRect::all(Val::Px(POINTER_BORDER)),
+        ..default()
+    };
+    place(&mut node, position, RIPPLE_FROM);
+    world.spawn((
+        node,
+        BorderColor::all(RIPPLE_COLOR),
+        BorderRadius::MAX,
+        GlobalZIndex(i32::MAX - 1),
+        Pickable::IGNORE,
+        TestOverlay,
+        Ripple {
+            center: position,
+            elapsed: 0.0,
+        },
+    ));
+}
+
+// 每帧让指针跟随最近的位置，推进波纹动画
+pub fn update_virtual_cursor(
+    mut commands: Commands,
+    cursor: Res<VirtualCursor>,
+    time: Res<Time>,
+    mut pointers: Query<&mut Node, (With<CursorPointer>, Without<Ripple>)>,
+    mut ripples: Query<(Entity, &mut Ripple, &mut Node, &mut BorderColor), Without<CursorPointer>>,
+) {
+    if !cursor.enabled {
+        return;
+    }
+    if let Some(position) = cursor.position {
+        match pointers.single_mut() {
+            Ok(mut node) => {
+                // 只在位置变化时写入，避免每帧触发布局
+                let mut placed = node.clone();
+                place(&mut placed, position, POINTER_SIZE);
+                node.set_if_neq(placed);
+            }
+            Err(_) => {
+                let mut node = Node {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(POINTER_BORDER)),
+                    ..default()
+                };
+                place(&mut node, position, POINTER_SIZE);
+                // 指针画在波纹与高亮之上
+                commands.spawn((
+                    node,
+                    BackgroundColor(Color::WHITE.with_alpha(0.85)),
+                    BorderColor::all(Color::BLACK),
+                    BorderRadius::MAX,
+                    GlobalZIndex(i32::MAX),
+                    Pickable::IGNORE,
+                    TestOverlay,
+                    CursorPointer,
+                ));
+            }
+        }
+    }
+
+    for (entity, mut ripple, mut node, mut border) in ripples.iter_mut() {
+        ripple.elapsed += time.delta_secs();
+        let t = ripple.elapsed / RIPPLE_SECONDS;
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        place(&mut node, ripple.center, RIPPLE_FROM.lerp(RIPPLE_TO, t));
+        *border = BorderColor::all(RIPPLE_COLOR.with_alpha(1.0 - t));
+    }
+}
+
+/// 以 center 为中心放置直径为 size 的圆形节点
+fn place(node: &mut Node, center: Vec2, size: f32) {
+    node.left = Val::Px(center.x - size / 2.0);
+    node.top = Val::Px(center.y - size / 2.0);
+    node.width = Val::Px(size);
+    node.height = Val::Px(size);
+}
@@ -0,0 +1,83 @@
+//! 游戏自定义测试工具：项目特有的操作（如"给玩家 100 金币"）不必修改 mcp/dispatch.rs，
+//! 在构建 App 时注册即可出现在 tools/list 中
+//!
+//! ```ignore
+//! app.register_test_tool(
+//!     "spawn_balls",
+//!     "生成 N 个小球",
+//!     json!({ "type": "object", "properties": { "count": { "type": "integer", "minimum": 1 } } }),
+//!     |world: &mut World, args: Value| {
+//!         // ...
+//!         Ok(json!({ "spawned": count }))
+//!     },
+//! );
+//! ```
+//!
+//! 处理函数在交互阶段（TestSystems::Interaction）以 `&mut World` 执行，游戏的 Update 系统本帧即可看到修改。
+//! 运行中注册（如在独占系统或 `commands.queue` 中对 World 调用）时向客户端发送 notifications/tools/list_changed
+
+use bevy::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::test_system::mcp;
+
+/// 自定义工具的处理函数：参数已通过 inputSchema 校验
+pub type TestToolHandler = Arc<dyn Fn(&mut World, Value) -> Result<Value, String> + Send + Sync>;
+
+/// 已注册的自定义工具处理函数
+#[derive(Resource, Default)]
+pub struct CustomTestTools(HashMap<String, TestToolHandler>);
+
+pub trait RegisterTestTool {
+    /// 注册自定义测试工具；schema 为工具的 inputSchema（JSON Schema 对象）。
+    /// 同名的自定义工具会被替换，与内置工具重名时记录错误并忽略
+    fn register_test_tool(
+        &mut self,
+        name: &str,
+        description: &str,
+        schema: Value,
+        handler: impl Fn(&mut World, Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterTestTool for World {
+    fn register_test_tool(
+        &mut self,
+        name: &str,
+        description: &str,
+        schema: Value,
+        handler: impl Fn(&mut World, Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        if mcp::register_custom_tool(name, description, schema).is_ok() {
+            self.get_resource_or_init::<CustomTestTools>()
+                .0
+                .insert(name.to_string(), Arc::new(handler));
+        }
+        self
+    }
+}
+
+impl RegisterTestTool for App {
+    fn register_test_tool(
+        &mut self,
+        name: &str,
+        description: &str,
+        schema: Value,
+        handler: impl Fn(&mut World, Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world_mut()
+            .register_test_tool(name, description, schema, handler);
+        self
+    }
+}
+
+/// 执行自定义工具
+pub fn run(world: &mut World, name: &str, args: Value) -> Result<Value, String> {
+    let handler = world
+        .get_resource::<CustomTestTools>()
+        .and_then(|tools| tools.0.get(name).cloned())
+        .ok_or_else(|| format!("未注册的自定义工具: {}", name))?;
+    handler(world, args)
+}
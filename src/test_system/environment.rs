@@ -0,0 +1,155 @@
+//! 渲染环境指纹：wgpu 适配器、后端、驱动、表面格式、缩放比例与实际加载的字体
+//!
+//! llvmpipe / xvfb 与真实 GPU 渲染出的画面不同，截图与基准图目录可以按指纹区分
+
+use bevy::diagnostic::FrameCount;
+use bevy::prelude::*;
+use bevy::render::renderer::RenderAdapterInfo;
+use bevy::render::view::ExtractedWindows;
+use bevy::window::PrimaryWindow;
+use log::info;
+use std::sync::{Arc, Mutex};
+
+use crate::font_manager::LoadedFont;
+use crate::headless::OffscreenTarget;
+use crate::test_system::channel::EnvironmentInfo;
+
+/// 等待表面格式的最长帧数，超过后即使未知也输出启动日志
+const LOG_AFTER_FRAMES: u32 = 60;
+
+/// 主窗口交换链的纹理格式，由渲染子应用写入
+#[derive(Resource, Clone, Default)]
+pub struct SurfaceFormat(Arc<Mutex<Option<String>>>);
+
+// 渲染子应用：记录主窗口交换链的纹理格式
+pub fn record_surface_format(windows: Res<ExtractedWindows>, shared: Res<SurfaceFormat>) {
+    let format = windows
+        .primary
+        .and_then(|entity| windows.get(&entity))
+        .and_then(|window| window.swap_chain_texture_format);
+    if let Some(format) = format {
+        let mut shared = shared.0.lock().unwrap();
+        if shared.is_none() {
+            *shared = Some(format!("{:?}", format));
+        }
+    }
+}
+
+/// 收集当前渲染环境
+pub fn collect(world: &mut World) -> EnvironmentInfo {
+    let (adapter, backend, device_type, driver, driver_info) =
+        match world.get_resource::<RenderAdapterInfo>() {
+            Some(info) => (
+                info.name.clone(),
+                info.backend.to_str().to_string(),
+                format!("{:?}", info.device_type),
+                info.driver.clone(),
+                info.driver_info.clone(),
+            ),
+            None => Default::default(),
+        };
+
+    let offscreen = world.get_resource::<OffscreenTarget>().cloned();
+    let headless = offscreen.is_some();
+    let surface_format = match offscreen {
+        // 无窗口模式下渲染目标就是离屏 Image
+        Some(target) => world
+            .resource::<Assets<Image>>()
+            .get(&target.0)
+            .map(|image| format!("{:?}", image.texture_descriptor.format)),
+        None => world
+            .get_resource::<SurfaceFormat>()
+            .and_then(|shared| shared.0.lock().unwrap().clone()),
+    };
+    let scale_factor = world
+        .query_filtered::<&Window, With<PrimaryWindow>>()
+        .iter(world)
+        .next()
+        .map_or(1.0, |window| window.scale_factor());
+    let font = world
+        .get_resource::<LoadedFont>()
+        .and_then(|font| font.path.clone());
+
+    let fingerprint = fingerprint(&backend, &adapter, scale_factor);
+    EnvironmentInfo {
+        adapter,
+        backend,
+        device_type,
+        driver,
+        driver_info,
+        surface_format,
+        scale_factor,
+        headless,
+        font,
+        fingerprint,
+    }
+}
+
+/// 指纹：后端、适配器名与缩放比例，如 `gl-llvmpipe-1x`，可直接用作目录名
+pub fn fingerprint(backend: &str, adapter: &str, scale_factor: f32) -> String {
+    // 适配器名里常带版本号等细节（如 "llvmpipe (LLVM 15.0.7, 256 bits)"），只取括号前的部分
+    let adapter = adapter.split('(').next().unwrap_or_default();
+    let slug = |s: &str| {
+        s.split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect::<Vec<_>>()
+            .join("-")
+    };
+    let adapter = match slug(adapter) {
+        s if s.is_empty() => "unknown".to_string(),
+        s => s
+            .chars()
+            .take(32)
+            .collect::<String>()
+            .trim_end_matches('-')
+            .to_string(),
+    };
+    let backend = match slug(backend) {
+        s if s.is_empty() => "unknown".to_string(),
+        s => s,
+    };
+    format!("{}-{}-{}x", backend, adapter, scale_factor)
+}
+
+// 表面格式可用后（或等待超时后）输出一次渲染环境日志
+pub fn log_environment(world: &mut World, mut logged: Local<bool>) {
+    if *logged {
+        return;
+    }
+    let info = collect(world);
+    if info.surface_format.is_none() && world.resource::<FrameCount>().0 < LOG_AFTER_FRAMES {
+        return;
+    }
+    *logged = true;
+    info!(
+        "渲染环境: 适配器={} 后端={} 类型={} 驱动={} {} 表面格式={} 缩放={} 字体={} 指纹={}",
+        info.adapter,
+        info.backend,
+        info.device_type,
+        info.driver,
+        info.driver_info,
+        info.surface_format.as_deref().unwrap_or("未知"),
+        info.scale_factor,
+        info.font.as_deref().unwrap_or("Bevy 内置默认字体"),
+        info.fingerprint
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_directory_safe() {
+        assert_eq!(
+            fingerprint("gl", "llvmpipe (LLVM 15.0.7, 256 bits)", 1.0),
+            "gl-llvmpipe-1x"
+        );
+        assert_eq!(
+            fingerprint("vulkan", "NVIDIA GeForce RTX 3060", 1.5),
+            "vulkan-nvidia-geforce-rtx-3060-1.5x"
+        );
+        assert_eq!(fingerprint("", "", 2.0), "unknown-unknown-2x");
+    }
+}
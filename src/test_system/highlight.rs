@@ -0,0 +1,115 @@
+//! 高亮覆盖层：在测试系统解析到的节点外绘制边框与标签，直到 clear_highlights
+
+use bevy::picking::Pickable;
+use bevy::prelude::*;
+
+/// 边框粗细（逻辑像素）
+const OUTLINE_WIDTH: f32 = 3.0;
+/// 标签字号
+const LABEL_SIZE: f32 = 14.0;
+
+/// 测试系统自己生成的 UI 节点，不出现在快照与选择器结果中
+#[derive(Component)]
+pub struct TestOverlay;
+
+/// 高亮覆盖层，跟随 target 节点的位置与尺寸
+#[derive(Component)]
+pub struct Highlight {
+    pub target: Entity,
+}
+
+/// 为节点生成高亮覆盖层
+pub fn spawn(world: &mut World, target: Entity, color: Color, label: Option<String>) {
+    let overlay = world
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(OUTLINE_WIDTH)),
+                ..default()
+            },
+            BorderColor::all(color),
+            GlobalZIndex(i32::MAX - 1),
+            Pickable::IGNORE,
+            TestOverlay,
+            Highlight { target },
+        ))
+        .id();
+    if let Some(label) = label {
+        world.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(-OUTLINE_WIDTH),
+                bottom: Val::Percent(100.0),
+                padding: UiRect::axes(Val::Px(4.0), Val::Px(1.0)),
+                ..default()
+            },
+            BackgroundColor(color),
+            Text::new(label),
+            TextFont {
+                font_size: LABEL_SIZE,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+            Pickable::IGNORE,
+            TestOverlay,
+            ChildOf(overlay),
+        ));
+    }
+    // 立即按目标当前的布局定位，避免第一帧出现在左上角
+    let rect = target_rect(world, target);
+    if let (Some(rect), Some(mut node)) = (rect, world.get_mut::<Node>(overlay)) {
+        place(&mut node, rect);
+    }
+}
+
+/// 移除所有高亮，返回移除的数量
+pub fn clear(world: &mut World) -> usize {
+    let overlays: Vec<Entity> = world
+        .query_filtered::<Entity, With<Highlight>>()
+        .iter(world)
+        .collect();
+    for &overlay in &overlays {
+        world.entity_mut(overlay).despawn();
+    }
+    overlays.len()
+}
+
+// 每帧让覆盖层跟随目标节点；目标被移除时一并移除
+pub fn update_highlights(
+    mut commands: Commands,
+    mut overlays: Query<(Entity, &Highlight, &mut Node)>,
+    targets: Query<(&ComputedNode, &UiGlobalTransform), Without<Highlight>>,
+) {
+    for (overlay, highlight, mut node) in overlays.iter_mut() {
+        match targets.get(highlight.target) {
+            Ok((computed, transform)) => {
+                // 只在位置变化时写入，避免每帧触发布局
+                let mut placed = node.clone();
+                place(&mut placed, logical_rect(computed, transform));
+                node.set_if_neq(placed);
+            }
+            Err(_) => commands.entity(overlay).despawn(),
+        }
+    }
+}
+
+/// 目标节点的逻辑像素矩形
+fn target_rect(world: &World, target: Entity) -> Option<Rect> {
+    let computed = world.get::<ComputedNode>(target)?;
+    let transform = world.get::<UiGlobalTransform>(target)?;
+    Some(logical_rect(computed, transform))
+}
+
+fn logical_rect(computed: &ComputedNode, transform: &UiGlobalTransform) -> Rect {
+    let scale = computed.inverse_scale_factor();
+    Rect::from_center_size(transform.translation * scale, computed.size() * scale)
+}
+
+/// 覆盖层的边框画在目标外侧
+fn place(node: &mut Node, rect: Rect) {
+    let rect = rect.inflate(OUTLINE_WIDTH);
+    node.left = Val::Px(rect.min.x);
+    node.top = Val::Px(rect.min.y);
+    node.width = Val::Px(rect.width());
+    node.height = Val::Px(rect.height());
+}
@@ -0,0 +1,272 @@
+//! tools/call 的调用上下文：进度通知、取消与超时
+//!
+//! handler 为每个 tools/call 建立 [`CallContext`]，在其作用域内执行工具；dispatch_shared::send
+//! 等待游戏响应时通过 [`wait`] 读取当前上下文：
+//! - `_meta.progressToken`：等待期间定期向该请求自己的输出流（POST 的 SSE 响应或 stdio）发送
+//!   notifications/progress；以普通 JSON 响应的请求没有可用的流，不发送进度
+//! - notifications/cancelled：按 requestId 取消进行中的调用
+//! - `timeout_ms` 参数：整个调用的截止时间，替代各命令默认的超时
+//!
+//! 取消或超时时丢弃响应通道的接收端，Bevy 侧据此（oneshot::Sender::is_closed）跳过尚未执行的命令，
+//! 并停止推进中的稳定截图。资源、提示模板等内部调用不在任何上下文中，使用默认超时
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{Duration, Instant};
+
+use super::stream;
+use super::tools::ToolError;
+
+/// 等待期间发送进度通知的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 取消原因的发送端，值为 Some 表示已取消
+type CancelSender = watch::Sender<Option<String>>;
+
+/// 进行中的 tools/call：JSON-RPC id → (调用序号, 取消发送端)，供 notifications/cancelled 查找
+static IN_FLIGHT: LazyLock<Mutex<HashMap<String, (u64, CancelSender)>>> =
+    LazyLock::new(Mutex::default);
+
+static NEXT_CALL: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    static CURRENT: CallContext;
+}
+
+/// 一次 tools/call 的上下文
+pub struct CallContext {
+    progress_token: Option<Value>,
+    /// 发起调用的请求的输出流，进度通知只发到这里
+    replies: Option<mpsc::UnboundedSender<Value>>,
+    started: Instant,
+    /// timeout_ms 参数给出的截止时间
+    deadline: Option<Instant>,
+    /// 取消原因，Some 表示已取消
+    cancelled: watch::Receiver<Option<String>>,
+    /// 已发送的最大进度值，保证进度单调递增
+    last_progress: AtomicU64,
+}
+
+/// 调用结束时注销登记
+pub struct InFlightGuard(Option<(String, u64)>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let Some((key, call)) = &self.0 else {
+            return;
+        };
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        // 同一 id 可能已被之后的调用重新登记
+        if in_flight.get(key).is_some_and(|(c, _)| c == call) {
+            in_flight.remove(key);
+        }
+    }
+}
+
+impl CallContext {
+    /// 为请求建立上下文；id 为 None（通知）时不可取消
+    pub fn new(
+        id: Option<&Value>,
+        progress_token: Option<Value>,
+        timeout_ms: Option<u64>,
+        replies: Option<mpsc::UnboundedSender<Value>>,
+    ) -> (Self, InFlightGuard) {
+        let (tx, rx) = watch::channel(None);
+        let registration = id.map(|id| {
+            let call = NEXT_CALL.fetch_add(1, Ordering::Relaxed);
+            IN_FLIGHT.lock().unwrap().insert(id.to_string(), (call, tx));
+            (id.to_string(), call)
+        });
+        let started = Instant::now();
+        let context = Self {
+            progress_token,
+            replies,
+            started,
+            deadline: timeout_ms.map(|ms| started + Duration::from_millis(ms)),
+            cancelled: rx,
+            last_progress: AtomicU64::new(0),
+        };
+        (context, InFlightGuard(registration))
+    }
+
+    /// 在上下文中执行调用
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        CURRENT.scope(self, f).await
+    }
+
+    fn notify_progress(&self, total: Option<Duration>, message: &str) {
+        let (Some(token), Some(replies)) = (&self.progress_token, &self.replies) else {
+            return;
+        };
+        // 以已用毫秒数作为进度，同一毫秒内的多条通知依次加一
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let previous = self.last_progress.fetch_max(elapsed, Ordering::Relaxed);
+        let progress = if elapsed > previous {
+            elapsed
+        } else {
+            self.last_progress.fetch_add(1, Ordering::Relaxed) + 1
+        };
+        let mut params = json!({
+            "progressToken": token,
+            "progress": progress,
+            "message": message
+        });
+        if let Some(total) = total {
+            params["total"] = json!((total.as_millis() as u64).max(progress));
+        }
+        let _ = replies.send(stream::notification("notifications/progress", params));
+    }
+}
+
+/// 处理 notifications/cancelled：取消对应 id 的进行中调用，返回是否找到
+pub fn cancel(request_id: &Value, reason: Option<&str>) -> bool {
+    let in_flight = IN_FLIGHT.lock().unwrap();
+    let Some((_, tx)) = in_flight.get(&request_id.to_string()) else {
+        return false;
+    };
+    tx.send_replace(Some(reason.unwrap_or("客户端取消").to_string()));
+    true
+}
+
+/// 报告一个处理步骤（如"截图完成，正在对比"）；不在上下文中或没有 progressToken 时忽略
+pub fn report(message: &str) {
+    let _ = CURRENT.try_with(|context| {
+        let total = context.deadline.map(|deadline| deadline - context.started);
+        context.notify_progress(total, message);
+    });
+}
+
+/// 等待游戏的响应：超过截止时间（timeout_ms 或 default_timeout）返回 timeout，
+/// 调用被取消时返回 cancelled；等待期间按 progressToken 发送进度
+pub async fn wait<T>(
+    mut rx: oneshot::Receiver<T>,
+    default_timeout: Duration,
+    what: &str,
+) -> Result<T, ToolError> {
+    let now = Instant::now();
+    let (started, deadline, mut cancelled) = CURRENT
+        .try_with(|context| {
+            (
+                context.started,
+                context.deadline,
+                Some(context.cancelled.clone()),
+            )
+        })
+        .unwrap_or((now, None, None));
+    let (deadline, limit) = match deadline {
+        Some(deadline) => (deadline, deadline - started),
+        None => (now + default_timeout, default_timeout),
+    };
+    let mut ticker = tokio::time::interval_at(now + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut rx => {
+                return result.map_err(|_| ToolError::channel_closed("接收失败: 命令未得到响应"));
+            }
+            reason = wait_cancelled(&mut cancelled) => {
+                return Err(ToolError::new("cancelled", format!("调用已取消: {}", reason)));
+            }
+            _ = tokio::time::sleep_until(deadline) => {
+                let timeout_ms = limit.as_millis() as u64;
+                return Err(ToolError::timeout(format!("等待{}超时（{} 毫秒）", what, timeout_ms))
+                    .with_details(json!({ "timeout_ms": timeout_ms })));
+            }
+            _ = ticker.tick() => {
+                let _ = CURRENT.try_with(|context| {
+                    context.notify_progress(Some(deadline - started), &format!("等待{}", what));
+                });
+            }
+        }
+    }
+}
+
+/// 等到调用被取消，返回取消原因；不在上下文中时永不返回
+async fn wait_cancelled(cancelled: &mut Option<watch::Receiver<Option<String>>>) -> String {
+    let Some(rx) = cancelled else {
+        return std::future::pending().await;
+    };
+    // 发送端随登记一起移除时调用已经结束，wait_for 返回错误
+    let reason = rx
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|r| r.clone());
+    match reason {
+        Some(reason) => reason,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait() {
+        // 不在上下文中：默认超时
+        let (_tx, rx) = oneshot::channel::<()>();
+        let error = wait(rx, Duration::from_secs(1), "游戏响应")
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, "timeout");
+        assert_eq!(error.details, Some(json!({ "timeout_ms": 1000 })));
+
+        // timeout_ms 替代默认超时
+        let id = json!("timeout");
+        let (context, _guard) = CallContext::new(Some(&id), None, Some(50), None);
+        let (_tx, rx) = oneshot::channel::<()>();
+        let error = context
+            .scope(wait(rx, Duration::from_secs(30), "游戏响应"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.details, Some(json!({ "timeout_ms": 50 })));
+
+        // 取消后接收端被丢弃，Bevy 侧可以跳过该命令
+        let id = json!(7);
+        let (context, guard) = CallContext::new(Some(&id), None, None, None);
+        let (tx, rx) = oneshot::channel::<()>();
+        let call = tokio::spawn(context.scope(wait(rx, Duration::from_secs(30), "游戏响应")));
+        tokio::task::yield_now().await;
+        assert!(cancel(&id, Some("用户中止")));
+        let error = call.await.unwrap().unwrap_err();
+        assert_eq!(error.code, "cancelled");
+        assert!(tx.is_closed());
+        drop(guard);
+        assert!(!cancel(&id, None));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_progress_goes_to_own_stream() {
+        let (a_tx, mut a_rx) = mpsc::unbounded_channel();
+        let (b_tx, mut b_rx) = mpsc::unbounded_channel();
+        let (a, _a_guard) =
+            CallContext::new(Some(&json!("a")), Some(json!("pa")), None, Some(a_tx));
+        let (b, _b_guard) =
+            CallContext::new(Some(&json!("b")), Some(json!("pb")), None, Some(b_tx));
+
+        // 两个调用同时等待，各自的进度只出现在各自的流中
+        let (a_reply, a_wait) = oneshot::channel::<()>();
+        let (b_reply, b_wait) = oneshot::channel::<()>();
+        let a_call = tokio::spawn(a.scope(wait(a_wait, Duration::from_secs(30), "游戏响应")));
+        let b_call = tokio::spawn(b.scope(wait(b_wait, Duration::from_secs(30), "游戏响应")));
+        tokio::time::sleep(PROGRESS_INTERVAL * 2 + Duration::from_millis(1)).await;
+        a_reply.send(()).unwrap();
+        b_reply.send(()).unwrap();
+        a_call.await.unwrap().unwrap();
+        b_call.await.unwrap().unwrap();
+
+        for (rx, token) in [(&mut a_rx, "pa"), (&mut b_rx, "pb")] {
+            let mut count = 0;
+            while let Ok(message) = rx.try_recv() {
+                assert_eq!(message["method"], "notifications/progress");
+                assert_eq!(message["params"]["progressToken"], token);
+                count += 1;
+            }
+            assert_eq!(count, 2);
+        }
+    }
+}
@@ -1,14 +1,23 @@
 //! MCP 工具调度编排层
 //!
-//! 具体实现分散在：
+//! 各子模块的工具在 REGISTRY 中注册（见 tools.rs），具体实现分散在：
 //! - dispatch_ui.rs      UI 交互：take_snapshot、screenshot、click、hover、fill、drag 等
 //! - dispatch_system.rs  系统/调试：component_counts、console_messages、evaluate_script
+//! - dispatch_visual.rs  视觉回归：compare_screenshot
 //! - dispatch_shared.rs  共享常量与辅助函数
+//!
+//! 游戏通过 register_test_tool 注册的自定义工具也在 REGISTRY 中，调用时经 TestMessage::CustomTool 转到 Bevy 主线程执行
 
 use crossbeam_channel::Sender;
-use serde_json::Value;
+use log::{error, info};
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::test_system::channel::{Settle, TestMessage, TestStage, CURRENT_FRAME};
 
-use crate::test_system::channel::TestMessage;
+use super::stream;
+use super::tools::{CallFn, ToolError, ToolRegistry};
 
 #[path = "dispatch_shared.rs"]
 mod dispatch_shared;
@@ -16,17 +25,90 @@ mod dispatch_shared;
 mod dispatch_system;
 #[path = "dispatch_ui.rs"]
 mod dispatch_ui;
+#[path = "dispatch_visual.rs"]
+mod dispatch_visual;
+
+pub use dispatch_shared::artifact_dir;
+
+/// 内置工具与游戏注册的自定义工具
+static REGISTRY: LazyLock<RwLock<ToolRegistry>> = LazyLock::new(|| {
+    let mut registry = ToolRegistry::default();
+    dispatch_ui::register(&mut registry);
+    dispatch_system::register(&mut registry);
+    dispatch_visual::register(&mut registry);
+    RwLock::new(registry)
+});
+
+/// tools/list 的结果
+pub fn tool_list() -> Value {
+    REGISTRY.read().unwrap().list()
+}
+
+/// 登记自定义工具并通知客户端工具列表已变化；与内置工具重名时返回错误
+pub fn register_custom_tool(
+    name: &str,
+    description: &str,
+    input_schema: Value,
+) -> Result<(), String> {
+    let tool_name = name.to_string();
+    let call: CallFn = Arc::new(move |sender, args| {
+        let name = tool_name.clone();
+        Box::pin(async move {
+            dispatch_shared::send(
+                sender,
+                |tx| TestMessage::CustomTool {
+                    name,
+                    args,
+                    response: tx,
+                },
+                dispatch_shared::TIMEOUT,
+            )
+            .await?
+            .map_err(|e| ToolError::new("tool_failed", e))
+        })
+    });
+    if let Err(e) = REGISTRY.write().unwrap().register_custom(
+        name.to_string(),
+        description.to_string(),
+        TestStage::Interaction,
+        input_schema,
+        call,
+    ) {
+        error!("注册自定义工具失败: {}", e);
+        return Err(e);
+    }
+    info!("已注册自定义工具: {}", name);
+    stream::notify("notifications/tools/list_changed", json!({}));
+    Ok(())
+}
+
+/// 工具执行结果及其对应的帧号
+pub struct ToolOutput {
+    pub data: Value,
+    /// 结果对应的 FrameCount，客户端可据此做 read-after-write 判断
+    pub frame: u32,
+}
 
 pub async fn call_tool(
     sender: &Sender<TestMessage>,
     name: &str,
     args: &Value,
-) -> Result<Value, String> {
-    if let Some(result) = dispatch_ui::handle(sender, name, args).await {
-        return result;
-    }
-    if let Some(result) = dispatch_system::handle(sender, name, args).await {
-        return result;
+) -> Result<ToolOutput, ToolError> {
+    let settle = dispatch_shared::arg_settle(args).map_err(|e| {
+        ToolError::invalid_argument(e).with_details(json!({ "argument": "settle" }))
+    })?;
+    let tool = REGISTRY
+        .read()
+        .unwrap()
+        .get(name)
+        .ok_or_else(|| ToolError::unknown_tool(name))?;
+    // 不等待 settle 时不发屏障，帧号取 Bevy 最近一次接收消息的帧
+    if settle == Settle::Immediate {
+        let data = tool.call(sender, args).await?;
+        let frame = CURRENT_FRAME.load(Ordering::Relaxed);
+        return Ok(ToolOutput { data, frame });
     }
-    Err(format!("未知工具: {}", name))
+    let (data, frame) =
+        dispatch_shared::with_settle(sender, settle, tool.call(sender, args)).await?;
+    Ok(ToolOutput { data, frame })
 }
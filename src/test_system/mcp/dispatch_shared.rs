@@ -1,48 +1,232 @@
 //! 各 dispatch 子模块共享的常量与辅助函数
 
 use crossbeam_channel::Sender;
-use serde_json::{json, Value};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
 use tokio::sync::oneshot;
 
-use crate::test_system::channel::TestMessage;
+use crate::test_system::channel::{EnvironmentInfo, Settle, TestMessage};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use super::super::context;
+use super::super::tools::ToolError;
 
 pub const TIMEOUT: u64 = 30;
 pub const SCREENSHOT_TIMEOUT: u64 = 10;
+/// 截图 base64 内联的默认上限（字节），可用 SCREENSHOT_MAX_INLINE_BYTES 环境变量覆盖
+pub const SCREENSHOT_MAX_INLINE_BYTES: usize = 2 * 1024 * 1024;
+
+tokio::task_local! {
+    static BARRIER: SettleBarrier;
+}
 
-/// 向 Bevy 主线程发送消息并等待响应
+/// 调用请求了 settle 时的帧同步屏障：每条命令发出后紧跟一条 Settle 消息，
+/// 两者在同一次 receive_test_messages 中被取出，等待帧数从命令被接收的那一帧算起
+struct SettleBarrier {
+    settle: Settle,
+    /// 最近一条命令之后的屏障
+    pending: Mutex<Option<oneshot::Receiver<u32>>>,
+}
+
+/// 在 settle 屏障作用域内执行工具，返回工具结果与满足 settle 时的帧号；
+/// 工具没有向 Bevy 发送命令时，屏障从当前帧算起
+pub async fn with_settle<T>(
+    tx: &Sender<TestMessage>,
+    settle: Settle,
+    f: impl Future<Output = Result<T, ToolError>>,
+) -> Result<(T, u32), ToolError> {
+    let barrier = SettleBarrier {
+        settle,
+        pending: Mutex::new(None),
+    };
+    let (result, barrier) = BARRIER
+        .scope(barrier, async {
+            let result = f.await;
+            (result, BARRIER.with(|b| b.pending.lock().unwrap().take()))
+        })
+        .await;
+    let data = result?;
+    let frame = match barrier {
+        Some(r) => context::wait(r, Duration::from_secs(TIMEOUT), "帧同步").await?,
+        None => {
+            send(
+                tx,
+                |s| TestMessage::Settle {
+                    settle,
+                    response: s,
+                },
+                TIMEOUT,
+            )
+            .await?
+        }
+    };
+    Ok((data, frame))
+}
+
+/// 向 Bevy 主线程发送消息并等待响应；通道关闭返回 channel_closed，超过 timeout 秒
+/// （或调用的 timeout_ms）返回 timeout，调用被取消时返回 cancelled
 pub async fn send<T: Send + 'static>(
     tx: &Sender<TestMessage>,
     make: impl FnOnce(oneshot::Sender<T>) -> TestMessage,
     timeout: u64,
-) -> Result<T, String> {
+) -> Result<T, ToolError> {
     let (s, r) = oneshot::channel();
-    tx.send(make(s)).map_err(|e| format!("发送失败: {}", e))?;
-    tokio::time::timeout(tokio::time::Duration::from_secs(timeout), r)
-        .await
-        .map_err(|_| "超时".to_string())?
-        .map_err(|_| "接收失败".to_string())
+    tx.send(make(s))
+        .map_err(|_| ToolError::channel_closed("发送失败: 游戏主循环已停止"))?;
+    let _ = BARRIER.try_with(|barrier| {
+        let (s, r) = oneshot::channel();
+        let settle = barrier.settle;
+        if tx
+            .send(TestMessage::Settle {
+                settle,
+                response: s,
+            })
+            .is_ok()
+        {
+            *barrier.pending.lock().unwrap() = Some(r);
+        }
+    });
+    context::wait(r, Duration::from_secs(timeout), "游戏响应").await
+}
+
+/// 截图内联上限：环境变量 SCREENSHOT_MAX_INLINE_BYTES 或默认值
+pub fn max_inline_bytes() -> usize {
+    std::env::var("SCREENSHOT_MAX_INLINE_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(SCREENSHOT_MAX_INLINE_BYTES)
+}
+
+/// 测试产物目录：与测试日志同目录下的 `sub/`
+pub fn artifact_dir(sub: &str) -> std::path::PathBuf {
+    let log_file = std::env::var("TEST_LOG_FILE").unwrap_or_else(|_| "logs/game.log".to_string());
+    std::path::Path::new(&log_file)
+        .parent()
+        .map(|p| p.join(sub))
+        .unwrap_or_else(|| sub.into())
+}
+
+/// 从 JSON args 中取 settle 参数："immediate" | "update" | "render" | 帧数 N
+pub fn arg_settle(args: &Value) -> Result<Settle, String> {
+    match &args["settle"] {
+        Value::Null => Ok(Settle::Immediate),
+        Value::String(s) => match s.as_str() {
+            "immediate" => Ok(Settle::Immediate),
+            "update" => Ok(Settle::Update),
+            "render" => Ok(Settle::Render),
+            other => Err(format!("无效的 settle: {}", other)),
+        },
+        Value::Number(n) => n
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(Settle::Frames)
+            .ok_or_else(|| format!("无效的 settle: {}", n)),
+        other => Err(format!("无效的 settle: {}", other)),
+    }
+}
+
+/// 元素选择器参数：单个 `id` 或多个 `ids`（同时给出时以 ids 为准）
+#[derive(Deserialize, JsonSchema)]
+pub struct SelectorArgs {
+    /// 元素标识或选择器：testId / Name / bits:xxxx，或 testId=… / name=… / text=… / type=button
+    pub id: Option<String>,
+    /// 多个元素标识或选择器（同一帧处理）
+    pub ids: Option<Vec<String>>,
 }
 
-/// 从 JSON args 中取字符串参数
-pub fn arg_str(args: &Value, k: &str) -> Result<String, String> {
-    args[k]
-        .as_str()
-        .map(|s| s.to_string())
-        .ok_or_else(|| format!("缺少参数: {}", k))
+impl SelectorArgs {
+    /// 选择器列表，可能为空
+    pub fn list(self) -> Vec<String> {
+        self.ids.unwrap_or_else(|| self.id.into_iter().collect())
+    }
+
+    /// 至少一个选择器
+    pub fn required(self) -> Result<Vec<String>, ToolError> {
+        let selectors = self.list();
+        if selectors.is_empty() {
+            return Err(ToolError::invalid_argument("缺少参数: id 或 ids"));
+        }
+        Ok(selectors)
+    }
 }
 
-/// 从 JSON args 中取 f32 数值参数
-pub fn arg_f32(args: &Value, k: &str) -> Result<f32, String> {
-    args[k]
-        .as_f64()
-        .map(|v| v as f32)
-        .ok_or_else(|| format!("缺少参数: {}", k))
+/// 交互操作的结果
+#[derive(Serialize, JsonSchema)]
+pub struct ActionOutput {
+    /// 是否成功
+    pub success: bool,
+    /// 操作名称，失败时带 "失败: " 前缀
+    pub message: String,
 }
 
 /// 构造布尔型操作结果
-pub fn bool_cmd(label: &str, ok: bool) -> Value {
-    json!({
-        "success": ok,
-        "message": format!("{}{}", if ok { "" } else { "失败: " }, label)
-    })
+pub fn bool_cmd(label: &str, ok: bool) -> ActionOutput {
+    ActionOutput {
+        success: ok,
+        message: format!("{}{}", if ok { "" } else { "失败: " }, label),
+    }
+}
+
+/// 查询渲染环境信息
+pub async fn environment_info(tx: &Sender<TestMessage>) -> Result<EnvironmentInfo, ToolError> {
+    send(
+        tx,
+        |tx| TestMessage::EnvironmentInfo { response: tx },
+        TIMEOUT,
+    )
+    .await
+}
+
+/// 把路径中的 `{fingerprint}` 替换为渲染环境指纹，使不同渲染器的截图互不混合
+pub async fn expand_fingerprint(
+    tx: &Sender<TestMessage>,
+    path: String,
+) -> Result<String, ToolError> {
+    // 指纹在进程内不变，查询一次后缓存
+    static FINGERPRINT: OnceLock<String> = OnceLock::new();
+    if !path.contains("{fingerprint}") {
+        return Ok(path);
+    }
+    let fingerprint = match FINGERPRINT.get() {
+        Some(fingerprint) => fingerprint.clone(),
+        None => {
+            let fingerprint = environment_info(tx).await?.fingerprint;
+            FINGERPRINT.get_or_init(|| fingerprint).clone()
+        }
+    };
+    Ok(path.replace("{fingerprint}", &fingerprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_barrier_follows_command() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let game = std::thread::spawn(move || {
+            let Ok(TestMessage::Exit { response }) = rx.recv() else {
+                panic!("第一条消息应为命令");
+            };
+            // 屏障紧跟在命令之后，与命令在同一帧被取出
+            let Ok(TestMessage::Settle {
+                settle,
+                response: barrier,
+            }) = rx.try_recv()
+            else {
+                panic!("命令之后应紧跟屏障");
+            };
+            assert_eq!(settle, Settle::Update);
+            response.send(()).unwrap();
+            barrier.send(7).unwrap();
+        });
+
+        let command = send(&tx, |s| TestMessage::Exit { response: s }, TIMEOUT);
+        let (_, frame) = with_settle(&tx, Settle::Update, command).await.unwrap();
+        assert_eq!(frame, 7);
+        game.join().unwrap();
+    }
 }
@@ -1,92 +1,213 @@
-//! 系统/调试工具：component_counts、console_messages、evaluate_script
+//! 系统/调试工具：component_counts、environment_info、console_messages、evaluate_script
 
 use crossbeam_channel::Sender;
-use serde_json::{json, Value};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-use crate::test_system::channel::{LogEntryData, TestMessage};
+use crate::test_system::channel::{LogEntryData, TestMessage, TestStage};
 
-use super::dispatch_shared::{arg_str, send, TIMEOUT};
+use super::super::context;
+use super::super::tools::{NoArgs, Tool, ToolError, ToolRegistry};
+use super::dispatch_shared::{environment_info, send, TIMEOUT};
 
-macro_rules! try_ok {
-    ($expr:expr) => {
-        match $expr {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
-        }
-    };
-}
-
-pub async fn handle(
-    sender: &Sender<TestMessage>,
-    name: &str,
-    args: &Value,
-) -> Option<Result<Value, String>> {
-    Some(match name {
-        "component_counts" => {
-            let mut list: Vec<Value> = match send(
-                sender,
-                |tx| TestMessage::QueryComponents { response: tx },
-                TIMEOUT,
-            )
-            .await
-            {
-                Ok(v) => v,
-                Err(e) => return Some(Err(e)),
-            }
-            .into_iter()
-            .map(|(n, c)| json!({ "name": n, "count": c }))
-            .collect();
-            list.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
-            Ok(list.into())
-        }
+pub fn register(registry: &mut ToolRegistry) {
+    registry
+        .register::<ComponentCounts>()
+        .register::<EnvironmentInfo>()
+        .register::<ConsoleMessages>()
+        .register::<EvaluateScript>();
+}
+
+struct ComponentCounts;
+
+#[derive(Serialize, JsonSchema)]
+struct ComponentCountsOutput {
+    /// 按组件名排序
+    components: Vec<ComponentCount>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ComponentCount {
+    /// 组件名，如 Ball、Button
+    name: String,
+    /// 带该组件的实体数量
+    count: usize,
+}
+
+impl Tool for ComponentCounts {
+    const NAME: &'static str = "component_counts";
+    const DESCRIPTION: &'static str = "查询游戏中各组件的实体数量（Ball、Button 等）";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = NoArgs;
+    type Output = ComponentCountsOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        _: NoArgs,
+    ) -> Result<ComponentCountsOutput, ToolError> {
+        let mut components: Vec<ComponentCount> = send(
+            sender,
+            |tx| TestMessage::QueryComponents { response: tx },
+            TIMEOUT,
+        )
+        .await?
+        .into_iter()
+        .map(|(name, count)| ComponentCount { name, count })
+        .collect();
+        components.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(ComponentCountsOutput { components })
+    }
+}
+
+struct EnvironmentInfo;
+
+/// 渲染环境信息，字段含义见 environment.rs
+#[derive(Serialize, JsonSchema)]
+struct EnvironmentOutput {
+    /// wgpu 适配器名
+    adapter: String,
+    /// vulkan / gl / metal / dx12 …
+    backend: String,
+    device_type: String,
+    driver: String,
+    driver_info: String,
+    /// 渲染目标的纹理格式（窗口模式下首帧渲染前为 null）
+    surface_format: Option<String>,
+    scale_factor: f32,
+    headless: bool,
+    /// 实际加载的字体路径（null 表示 Bevy 内置字体）
+    font: Option<String>,
+    /// 可用作目录名的环境指纹，如 gl-llvmpipe-1x
+    fingerprint: String,
+}
+
+impl Tool for EnvironmentInfo {
+    const NAME: &'static str = "environment_info";
+    const DESCRIPTION: &'static str = "渲染环境信息：wgpu 适配器、后端（Vulkan/GL）、驱动、表面格式、缩放比例、实际加载的字体，以及可用作目录名的 fingerprint。screenshot 的 path 与 compare_screenshot 的 baseline_dir 中的 {fingerprint} 会被替换为该值";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = NoArgs;
+    type Output = EnvironmentOutput;
+
+    async fn call(sender: &Sender<TestMessage>, _: NoArgs) -> Result<EnvironmentOutput, ToolError> {
+        let info = environment_info(sender).await?;
+        Ok(EnvironmentOutput {
+            adapter: info.adapter,
+            backend: info.backend,
+            device_type: info.device_type,
+            driver: info.driver,
+            driver_info: info.driver_info,
+            surface_format: info.surface_format,
+            scale_factor: info.scale_factor,
+            headless: info.headless,
+            font: info.font,
+            fingerprint: info.fingerprint,
+        })
+    }
+}
+
+struct ConsoleMessages;
 
-        "console_messages" => {
-            let lines = args["lines"].as_u64().unwrap_or(50) as u32;
-            let log_file = args["log_file"].as_str().map(String::from);
-
-            let (tx, rx) = tokio::sync::oneshot::channel::<Vec<LogEntryData>>();
-            std::thread::spawn(move || {
-                let file_path = log_file.unwrap_or_else(|| {
-                    std::env::var("TEST_LOG_FILE").unwrap_or_else(|_| "logs/game.log".to_string())
-                });
-                let entries = read_log_file(&file_path, lines);
-                let _ = tx.send(entries);
+#[derive(Deserialize, JsonSchema)]
+struct ConsoleMessagesArgs {
+    /// 返回行数
+    #[serde(default = "default_lines")]
+    lines: u32,
+    /// 日志文件路径（可选）
+    log_file: Option<String>,
+}
+
+fn default_lines() -> u32 {
+    50
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ConsoleMessagesOutput {
+    /// 最近的日志，按时间顺序
+    entries: Vec<LogEntry>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct LogEntry {
+    timestamp: String,
+    /// INFO / WARN / ERROR …
+    level: String,
+    message: String,
+}
+
+impl Tool for ConsoleMessages {
+    const NAME: &'static str = "console_messages";
+    const DESCRIPTION: &'static str =
+        "读取后端游戏日志文件，返回最近 N 行（类 CDP list_console_messages）";
+    type Args = ConsoleMessagesArgs;
+    type Output = ConsoleMessagesOutput;
+
+    async fn call(
+        _: &Sender<TestMessage>,
+        args: ConsoleMessagesArgs,
+    ) -> Result<ConsoleMessagesOutput, ToolError> {
+        let ConsoleMessagesArgs { lines, log_file } = args;
+        let (tx, rx) = tokio::sync::oneshot::channel::<Vec<LogEntryData>>();
+        std::thread::spawn(move || {
+            let file_path = log_file.unwrap_or_else(|| {
+                std::env::var("TEST_LOG_FILE").unwrap_or_else(|_| "logs/game.log".to_string())
             });
+            let entries = read_log_file(&file_path, lines);
+            let _ = tx.send(entries);
+        });
 
-            let entries =
-                match tokio::time::timeout(tokio::time::Duration::from_secs(TIMEOUT), rx).await {
-                    Ok(Ok(v)) => v,
-                    Ok(Err(_)) => return Some(Err("读取日志失败".to_string())),
-                    Err(_) => return Some(Err("读取日志超时".to_string())),
-                };
+        let entries = context::wait(rx, Duration::from_secs(TIMEOUT), "读取日志").await?;
 
-            Ok(entries
+        Ok(ConsoleMessagesOutput {
+            entries: entries
                 .into_iter()
-                .map(
-                    |e| json!({ "timestamp": e.timestamp, "level": e.level, "message": e.message }),
-                )
-                .collect::<Vec<_>>()
-                .into())
-        }
+                .map(|e| LogEntry {
+                    timestamp: e.timestamp,
+                    level: e.level,
+                    message: e.message,
+                })
+                .collect(),
+        })
+    }
+}
 
-        "evaluate_script" => {
-            let script = try_ok!(arg_str(args, "script"));
-            let result = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::EvaluateScript {
-                        script,
-                        response: tx
-                    },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(json!({ "result": result }))
-        }
+struct EvaluateScript;
+
+#[derive(Deserialize, JsonSchema)]
+struct EvaluateScriptArgs {
+    /// JavaScript 代码
+    script: String,
+}
 
-        _ => return None,
-    })
+#[derive(Serialize, JsonSchema)]
+struct EvaluateScriptOutput {
+    /// 前端返回的执行结果
+    result: String,
+}
+
+impl Tool for EvaluateScript {
+    const NAME: &'static str = "evaluate_script";
+    const DESCRIPTION: &'static str =
+        "在 Tauri 前端 WebView 执行 JavaScript（需设置 JS_EVALUATOR_URL 环境变量）";
+    const STAGE: Option<TestStage> = Some(TestStage::Input);
+    type Args = EvaluateScriptArgs;
+    type Output = EvaluateScriptOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: EvaluateScriptArgs,
+    ) -> Result<EvaluateScriptOutput, ToolError> {
+        let result = send(
+            sender,
+            |tx| TestMessage::EvaluateScript {
+                script: args.script,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(EvaluateScriptOutput { result })
+    }
 }
 
 fn read_log_file(file_path: &str, lines: u32) -> Vec<LogEntryData> {
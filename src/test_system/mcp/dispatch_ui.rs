@@ -1,223 +1,918 @@
+//! UI 工具：take_snapshot、screenshot、element_screenshot、click、hover、fill、drag、highlight 等
+
 use crossbeam_channel::Sender;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::test_system::channel::TestMessage;
+use crate::test_system::channel::{
+    AnnotationMark, ImageEncoding, ScreenshotOptions, Stability, StableOptions, TestMessage,
+    TestStage, UINodeData,
+};
+use crate::test_system::screenshot::write_file;
+
+use super::super::context;
+use super::super::tools::{NoArgs, Tool, ToolError, ToolRegistry};
+use super::dispatch_shared::{
+    artifact_dir, bool_cmd, expand_fingerprint, max_inline_bytes, send, ActionOutput, SelectorArgs,
+    SCREENSHOT_TIMEOUT, TIMEOUT,
+};
 
-use super::dispatch_shared::{arg_f32, arg_str, bool_cmd, send, SCREENSHOT_TIMEOUT, TIMEOUT};
+pub fn register(registry: &mut ToolRegistry) {
+    registry
+        .register::<Health>()
+        .register::<TakeSnapshot>()
+        .register::<Screenshot>()
+        .register::<ElementScreenshot>()
+        .register::<Click>()
+        .register::<Hover>()
+        .register::<ClickById>()
+        .register::<HoverById>()
+        .register::<ClickButton>()
+        .register::<PressKey>()
+        .register::<Fill>()
+        .register::<Drag>()
+        .register::<Highlight>()
+        .register::<ClearHighlights>()
+        .register::<VirtualCursor>();
+}
+
+/// 图像格式（webp 为无损）
+#[derive(Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
 
-macro_rules! try_ok {
-    ($expr:expr) => {
-        match $expr {
-            Ok(v) => v,
-            Err(e) => return Some(Err(e)),
+impl ImageFormat {
+    fn encoding(self, quality: u8) -> ImageEncoding {
+        match self {
+            ImageFormat::Png => ImageEncoding::Png,
+            ImageFormat::Jpeg => ImageEncoding::Jpeg { quality },
+            ImageFormat::Webp => ImageEncoding::WebP,
         }
-    };
+    }
 }
 
-pub async fn handle(
-    sender: &Sender<TestMessage>,
-    name: &str,
-    args: &Value,
-) -> Option<Result<Value, String>> {
-    Some(match name {
-        "health" => Ok(json!({ "status": "OK" })),
+fn default_quality() -> u8 {
+    80
+}
 
-        "take_snapshot" => {
-            let nodes = match send(
-                sender,
-                |tx| TestMessage::TakeSnapshot { response: tx },
-                TIMEOUT,
-            )
-            .await
-            {
-                Ok(v) => v,
-                Err(e) => return Some(Err(e)),
-            };
-            Ok(nodes
-                .into_iter()
-                .map(|n| {
-                    json!({
-                        "uid": n.uid,
-                        "name": n.name,
-                        "nodeType": n.node_type,
-                        "text": n.text,
-                        "testId": n.test_id,
-                        "visible": n.visible,
-                        "x": n.x,
-                        "y": n.y,
-                        "width": n.width,
-                        "height": n.height,
-                        "parentUid": n.parent_uid,
-                    })
-                })
-                .collect::<Vec<_>>()
-                .into())
+/// 等待画面稳定：true、连续帧数 N，或 {frames, tolerance, timeout_ms}
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum StableArg {
+    Enabled(bool),
+    Frames(#[schemars(range(min = 2))] u32),
+    Options(StableArgOptions),
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct StableArgOptions {
+    /// 需要连续一致的帧数
+    #[serde(default = "default_stable_frames")]
+    #[schemars(range(min = 2))]
+    frames: u32,
+    /// 单通道允许的差值
+    #[serde(default)]
+    tolerance: u8,
+    /// 超时（毫秒）
+    #[serde(default = "default_stable_timeout_ms")]
+    timeout_ms: u64,
+}
+
+fn default_stable_frames() -> u32 {
+    StableOptions::default().frames
+}
+
+fn default_stable_timeout_ms() -> u64 {
+    StableOptions::default().timeout_ms
+}
+
+impl StableArg {
+    fn options(self) -> Option<StableOptions> {
+        match self {
+            StableArg::Enabled(false) => None,
+            StableArg::Enabled(true) => Some(StableOptions::default()),
+            StableArg::Frames(frames) => Some(StableOptions {
+                frames,
+                ..StableOptions::default()
+            }),
+            StableArg::Options(options) => Some(StableOptions {
+                frames: options.frames,
+                tolerance: options.tolerance,
+                timeout_ms: options.timeout_ms,
+            }),
+        }
+    }
+}
+
+struct Health;
+
+#[derive(Serialize, JsonSchema)]
+struct HealthOutput {
+    /// 服务器运行时为 OK
+    status: String,
+}
+
+impl Tool for Health {
+    const NAME: &'static str = "health";
+    const DESCRIPTION: &'static str = "检查游戏测试服务器是否运行";
+    type Args = NoArgs;
+    type Output = HealthOutput;
+
+    async fn call(_: &Sender<TestMessage>, _: NoArgs) -> Result<HealthOutput, ToolError> {
+        Ok(HealthOutput {
+            status: "OK".to_string(),
+        })
+    }
+}
+
+struct TakeSnapshot;
+
+#[derive(Serialize, JsonSchema)]
+struct SnapshotOutput {
+    /// 所有 UI 节点（扁平列表，按 parent_uid 构建树）
+    nodes: Vec<SnapshotNode>,
+}
+
+/// UI 节点；x / y / width / height 为逻辑像素，只有 rect_px 为物理像素
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct SnapshotNode {
+    /// 唯一标识 bits:xxxx，可用于 click_by_id
+    uid: String,
+    /// Name 组件值，否则与 uid 相同
+    name: String,
+    /// button / text / container / root
+    node_type: String,
+    /// Text 组件内容
+    text: Option<String>,
+    /// TestId 组件值
+    test_id: Option<String>,
+    visible: bool,
+    /// Button 或带 Interaction 组件
+    interactive: bool,
+    /// 节点中心 X（逻辑像素，可直接传给 click / hover）
+    x: f32,
+    /// 节点中心 Y（逻辑像素）
+    y: f32,
+    /// 宽度（逻辑像素）
+    width: f32,
+    /// 高度（逻辑像素）
+    height: f32,
+    /// 节点矩形（物理像素，与截图像素一致），未完成布局时为 null
+    rect_px: Option<PixelRect>,
+    /// 父节点 uid（根节点为 null）
+    parent_uid: Option<String>,
+}
+
+/// 截图上的矩形：左上角与尺寸（物理像素）
+#[derive(Serialize, JsonSchema)]
+struct PixelRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl From<UINodeData> for SnapshotNode {
+    fn from(n: UINodeData) -> Self {
+        Self {
+            uid: n.uid,
+            name: n.name,
+            node_type: n.node_type,
+            text: n.text,
+            test_id: n.test_id,
+            visible: n.visible,
+            interactive: n.interactive,
+            x: n.x,
+            y: n.y,
+            width: n.width,
+            height: n.height,
+            rect_px: n.rect_px.map(|r| PixelRect {
+                x: r.min.x,
+                y: r.min.y,
+                width: r.width(),
+                height: r.height(),
+            }),
+            parent_uid: n.parent_uid,
         }
+    }
+}
 
-        "screenshot" => {
-            let path = match arg_str(args, "path") {
-                Ok(v) => v,
-                Err(e) => return Some(Err(e)),
+impl Tool for TakeSnapshot {
+    const NAME: &'static str = "take_snapshot";
+    const DESCRIPTION: &'static str = "获取游戏 UI 节点树快照（类 CDP take_snapshot）。返回 nodes：uid、name、node_type、text、test_id、visible、interactive、x/y（节点中心）与 width/height（逻辑像素，与 click / hover 坐标一致）、rect_px（左上角与尺寸，物理像素，与截图像素一致）、parent_uid。";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = NoArgs;
+    type Output = SnapshotOutput;
+
+    async fn call(sender: &Sender<TestMessage>, _: NoArgs) -> Result<SnapshotOutput, ToolError> {
+        let nodes = send(
+            sender,
+            |tx| TestMessage::TakeSnapshot { response: tx },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(SnapshotOutput {
+            nodes: nodes.into_iter().map(SnapshotNode::from).collect(),
+        })
+    }
+}
+
+struct Screenshot;
+
+#[derive(Deserialize, JsonSchema)]
+struct ScreenshotArgs {
+    /// 保存路径（可选），如 screenshots/test.png；{fingerprint} 会替换为渲染环境指纹
+    path: Option<String>,
+    /// 图像格式（webp 为无损），默认 png
+    #[serde(default)]
+    format: ImageFormat,
+    /// JPEG 质量
+    #[serde(default = "default_quality")]
+    #[schemars(range(min = 1, max = 100))]
+    quality: u8,
+    /// 缩放比例
+    #[schemars(range(max = 1), extend("exclusiveMinimum" = 0))]
+    scale: Option<f32>,
+    /// 最大宽度（像素），超过时等比缩小
+    #[schemars(range(min = 1))]
+    max_width: Option<u32>,
+    /// base64 内联上限（字节），默认 2 MiB 或 SCREENSHOT_MAX_INLINE_BYTES
+    max_inline_bytes: Option<u64>,
+    /// 为每个可见的可交互节点绘制编号框与 testId / uid 标签（set-of-marks），并返回编号 → id 图例，id 可直接用于 click_by_id
+    #[serde(default)]
+    annotate: bool,
+    /// 等待画面稳定后再返回：true（连续 3 帧一致）、帧数 N，或 {frames, tolerance, timeout_ms}。超时（默认 2000 ms）后返回最后一帧
+    stable: Option<StableArg>,
+}
+
+impl Tool for Screenshot {
+    const NAME: &'static str = "screenshot";
+    const DESCRIPTION: &'static str =
+        "截取游戏画面，直接从内存返回图像（不超过内联上限时 base64 内联，否则返回文件路径）";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = ScreenshotArgs;
+    type Output = Value;
+
+    async fn call(sender: &Sender<TestMessage>, args: ScreenshotArgs) -> Result<Value, ToolError> {
+        let path = match args.path {
+            Some(path) => Some(expand_fingerprint(sender, path).await?),
+            None => None,
+        };
+        let options = ScreenshotOptions {
+            path,
+            encoding: args.format.encoding(args.quality),
+            scale: args.scale,
+            max_width: args.max_width,
+            stable: args.stable.and_then(StableArg::options),
+            annotate: args.annotate,
+        };
+        let max_inline = args
+            .max_inline_bytes
+            .map(|v| v as usize)
+            .unwrap_or_else(max_inline_bytes);
+        let result = send(
+            sender,
+            |tx| TestMessage::Screenshot {
+                options: options.clone(),
+                response: tx,
+            },
+            SCREENSHOT_TIMEOUT
+                + options
+                    .stable
+                    .map_or(0, |stable| stable.timeout_ms.div_ceil(1000)),
+        )
+        .await?;
+        let image = result.map_err(|e| {
+            let path = options.path.clone();
+            ToolError::from(e).with_details(json!({ "path": path }))
+        })?;
+        let summary = format!(
+            "{}x{} {}，{} 字节{}",
+            image.width,
+            image.height,
+            image.mime_type,
+            image.bytes.len(),
+            stability_note(image.stability)
+        );
+        let legend = legend_json(&image.marks);
+        if image.bytes.len() <= max_inline {
+            // 对标 Chrome DevTools MCP screenshot.ts：小图直接 base64 内联
+            use base64::Engine;
+            let data = base64::engine::general_purpose::STANDARD.encode(&image.bytes);
+            let mut text = match &options.path {
+                Some(path) => format!("截图已保存: {}（{}）", path, summary),
+                None => format!("截图完成（{}）", summary),
             };
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::Screenshot {
-                        path: path.clone(),
-                        response: tx
-                    },
-                    SCREENSHOT_TIMEOUT,
-                )
-                .await
-            );
-            if !ok {
-                return Some(Ok(json!({
-                    "success": false,
-                    "path": path,
-                    "message": format!("截图失败: {}", path)
-                })));
-            }
-            // 对标 Chrome DevTools MCP screenshot.ts：
-            // 读取文件字节并 base64 内联（永远内联，不做大小限制）
-            match std::fs::read(&path) {
-                Ok(bytes) => {
-                    use base64::Engine;
-                    let data = base64::engine::general_purpose::STANDARD.encode(&bytes);
-                    Ok(json!({
-                        "__mcp_image": {
-                            "data": data,
-                            "mimeType": "image/png"
-                        },
-                        "text": format!("截图已保存: {}", path)
-                    }))
-                }
-                Err(e) => Ok(json!({
-                    "success": true,
-                    "path": path,
-                    "message": format!("截图已保存但无法读取文件: {}", e)
-                })),
+            if options.annotate {
+                text.push_str(&format!(
+                    "\n标注图例（编号 → id，id 可直接用于 click_by_id / hover_by_id）：\n{}",
+                    serde_json::to_string_pretty(&legend).unwrap_or_default()
+                ));
             }
+            return Ok(json!({
+                "__mcp_image": {
+                    "data": data,
+                    "mime_type": image.mime_type
+                },
+                "text": text
+            }));
         }
+        // 超过内联上限：返回文件引用，未指定 path 时写入默认截图目录
+        let path = match options.path {
+            Some(path) => path,
+            None => {
+                context::report("截图超过内联上限，正在写入文件");
+                let path = default_screenshot_path(options.encoding.extension());
+                let write_path = path.clone();
+                let bytes = image.bytes.clone();
+                tokio::task::spawn_blocking(move || write_file(&write_path, &bytes))
+                    .await
+                    .map_err(|e| ToolError::internal(format!("写入截图失败: {}", e)))??;
+                path
+            }
+        };
+        Ok(json!({
+            "success": true,
+            "inline": false,
+            "path": path,
+            "mime_type": image.mime_type,
+            "width": image.width,
+            "height": image.height,
+            "bytes": image.bytes.len(),
+            "stable": image.stability.map(|s| s.stable),
+            "legend": options.annotate.then_some(legend),
+            "message": format!("截图超过内联上限 {} 字节，已保存: {}（{}）", max_inline, path, summary)
+        }))
+    }
+}
 
-        "click" => {
-            let x = try_ok!(arg_f32(args, "x"));
-            let y = try_ok!(arg_f32(args, "y"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::Click { x, y, response: tx },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("click", ok))
-        }
+struct ElementScreenshot;
 
-        "hover" => {
-            let x = try_ok!(arg_f32(args, "x"));
-            let y = try_ok!(arg_f32(args, "y"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::Hover { x, y, response: tx },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("hover", ok))
-        }
+#[derive(Deserialize, JsonSchema)]
+struct ElementScreenshotArgs {
+    #[serde(flatten)]
+    selectors: SelectorArgs,
+    /// 四周留白（逻辑像素）
+    #[serde(default)]
+    #[schemars(range(min = 0))]
+    padding: f32,
+    /// 图像格式（webp 为无损），默认 png
+    #[serde(default)]
+    format: ImageFormat,
+    /// JPEG 质量
+    #[serde(default = "default_quality")]
+    #[schemars(range(min = 1, max = 100))]
+    quality: u8,
+}
 
-        "click_by_id" => {
-            let id = try_ok!(arg_str(args, "id"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::ClickById { id, response: tx },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("click_by_id", ok))
-        }
+impl Tool for ElementScreenshot {
+    const NAME: &'static str = "element_screenshot";
+    const DESCRIPTION: &'static str =
+        "截取一帧并按 UI 节点的 ComputedNode 矩形（物理像素）裁剪，一次渲染可截取多个元素";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = ElementScreenshotArgs;
+    type Output = Value;
 
-        "hover_by_id" => {
-            let id = try_ok!(arg_str(args, "id"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::HoverById { id, response: tx },
-                    TIMEOUT
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: ElementScreenshotArgs,
+    ) -> Result<Value, ToolError> {
+        let selectors = args.selectors.required()?;
+        let encoding = args.format.encoding(args.quality);
+        let elements = send(
+            sender,
+            |tx| TestMessage::ElementScreenshot {
+                selectors,
+                padding: args.padding,
+                encoding,
+                response: tx,
+            },
+            SCREENSHOT_TIMEOUT,
+        )
+        .await??;
+        use base64::Engine;
+        let images: Vec<Value> = elements
+            .iter()
+            .map(|el| {
+                json!({
+                    "data": base64::engine::general_purpose::STANDARD.encode(&el.image.bytes),
+                    "mime_type": el.image.mime_type
+                })
+            })
+            .collect();
+        let summary: Vec<String> = elements
+            .iter()
+            .enumerate()
+            .map(|(i, el)| {
+                format!(
+                    "#{} {} ({}) @ {},{} {}x{}",
+                    i + 1,
+                    el.selector,
+                    el.uid,
+                    el.x,
+                    el.y,
+                    el.width,
+                    el.height
                 )
-                .await
-            );
-            Ok(bool_cmd("hover_by_id", ok))
-        }
+            })
+            .collect();
+        Ok(json!({
+            "__mcp_image": images,
+            "text": format!("元素截图 {} 张（物理像素）:\n{}", elements.len(), summary.join("\n"))
+        }))
+    }
+}
 
-        "click_button" => {
-            let button_name = try_ok!(arg_str(args, "button_name"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::ClickButtonByName {
-                        button_name,
-                        response: tx
-                    },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("click_button", ok))
-        }
+#[derive(Deserialize, JsonSchema)]
+struct PointArgs {
+    /// 屏幕 X 坐标（像素）
+    x: f32,
+    /// 屏幕 Y 坐标（像素）
+    y: f32,
+}
 
-        "press_key" => {
-            let key = try_ok!(arg_str(args, "key"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::PressKey { key, response: tx },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("press_key", ok))
-        }
+struct Click;
 
-        "fill" => {
-            let id = try_ok!(arg_str(args, "id"));
-            let value = try_ok!(arg_str(args, "value"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::FillText {
-                        id,
-                        value,
-                        response: tx
-                    },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("fill", ok))
-        }
+impl Tool for Click {
+    const NAME: &'static str = "click";
+    const DESCRIPTION: &'static str = "在屏幕坐标 (x, y) 处点击";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = PointArgs;
+    type Output = ActionOutput;
 
-        "drag" => {
-            let from_id = try_ok!(arg_str(args, "from_id"));
-            let to_id = try_ok!(arg_str(args, "to_id"));
-            let ok = try_ok!(
-                send(
-                    sender,
-                    |tx| TestMessage::Drag {
-                        from_id,
-                        to_id,
-                        response: tx
-                    },
-                    TIMEOUT
-                )
-                .await
-            );
-            Ok(bool_cmd("drag", ok))
+    async fn call(
+        sender: &Sender<TestMessage>,
+        PointArgs { x, y }: PointArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        let ok = send(
+            sender,
+            |tx| TestMessage::Click { x, y, response: tx },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(bool_cmd("click", ok))
+    }
+}
+
+struct Hover;
+
+impl Tool for Hover {
+    const NAME: &'static str = "hover";
+    const DESCRIPTION: &'static str = "将鼠标悬停在屏幕坐标 (x, y) 处";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = PointArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        PointArgs { x, y }: PointArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        let ok = send(
+            sender,
+            |tx| TestMessage::Hover { x, y, response: tx },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(bool_cmd("hover", ok))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct IdArgs {
+    /// 元素标识：testId / Name / bits:xxxx
+    id: String,
+}
+
+struct ClickById;
+
+impl Tool for ClickById {
+    const NAME: &'static str = "click_by_id";
+    const DESCRIPTION: &'static str =
+        "按 test_id / Name / uid(bits:xxxx) 点击 UI 元素（类 CDP click(uid)）";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = IdArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        IdArgs { id }: IdArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::ClickById { id, response: tx },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("click_by_id", true))
+    }
+}
+
+struct HoverById;
+
+impl Tool for HoverById {
+    const NAME: &'static str = "hover_by_id";
+    const DESCRIPTION: &'static str = "按 test_id / Name / uid(bits:xxxx) 悬停 UI 元素";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = IdArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        IdArgs { id }: IdArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::HoverById { id, response: tx },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("hover_by_id", true))
+    }
+}
+
+struct ClickButton;
+
+#[derive(Deserialize, JsonSchema)]
+struct ClickButtonArgs {
+    /// 按钮名称，如 main-button
+    button_name: String,
+}
+
+impl Tool for ClickButton {
+    const NAME: &'static str = "click_button";
+    const DESCRIPTION: &'static str = "按按钮的 Name 或 test_id 点击按钮";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = ClickButtonArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: ClickButtonArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::ClickButtonByName {
+                button_name: args.button_name,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("click_button", true))
+    }
+}
+
+struct PressKey;
+
+#[derive(Deserialize, JsonSchema)]
+struct PressKeyArgs {
+    /// 按键名，如 Space、Enter、ArrowUp
+    key: String,
+}
+
+impl Tool for PressKey {
+    const NAME: &'static str = "press_key";
+    const DESCRIPTION: &'static str = "模拟键盘按键。支持：Space、Enter、Escape、Tab、Backspace、Delete、ArrowUp/Down/Left/Right、F1-F12、KeyA-Z、Digit0-9";
+    const STAGE: Option<TestStage> = Some(TestStage::Input);
+    type Args = PressKeyArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        PressKeyArgs { key }: PressKeyArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::PressKey { key, response: tx },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("press_key", true))
+    }
+}
+
+struct Fill;
+
+#[derive(Deserialize, JsonSchema)]
+struct FillArgs {
+    /// 元素标识
+    id: String,
+    /// 要填充的文本
+    value: String,
+}
+
+impl Tool for Fill {
+    const NAME: &'static str = "fill";
+    const DESCRIPTION: &'static str = "向指定 UI 元素（Text 组件）填充文本（先清空原内容）";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = FillArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        FillArgs { id, value }: FillArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::FillText {
+                id,
+                value,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("fill", true))
+    }
+}
+
+struct Drag;
+
+#[derive(Deserialize, JsonSchema)]
+struct DragArgs {
+    /// 源元素标识
+    from_id: String,
+    /// 目标元素标识
+    to_id: String,
+}
+
+impl Tool for Drag {
+    const NAME: &'static str = "drag";
+    const DESCRIPTION: &'static str = "从源元素拖拽到目标元素";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = DragArgs;
+    type Output = ActionOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        DragArgs { from_id, to_id }: DragArgs,
+    ) -> Result<ActionOutput, ToolError> {
+        send(
+            sender,
+            |tx| TestMessage::Drag {
+                from_id,
+                to_id,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await??;
+        Ok(bool_cmd("drag", true))
+    }
+}
+
+struct Highlight;
+
+#[derive(Deserialize, JsonSchema)]
+struct HighlightArgs {
+    #[serde(flatten)]
+    selectors: SelectorArgs,
+    /// #rrggbb、r,g,b（0-1）或颜色名 red / green / blue / yellow / magenta / cyan / orange / white
+    #[serde(default = "default_highlight_color")]
+    color: String,
+    /// 显示在边框上方的标签
+    label: Option<String>,
+}
+
+fn default_highlight_color() -> String {
+    "magenta".to_string()
+}
+
+#[derive(Serialize, JsonSchema)]
+struct HighlightOutput {
+    success: bool,
+    /// 各选择器匹配并高亮的节点数
+    highlights: Vec<HighlightMatch>,
+    message: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct HighlightMatch {
+    id: String,
+    matched: usize,
+}
+
+impl Tool for Highlight {
+    const NAME: &'static str = "highlight";
+    const DESCRIPTION: &'static str = "在选择器匹配的元素外绘制高亮边框与可选标签，保留到 clear_highlights，便于在截图中确认测试系统解析到的节点";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = HighlightArgs;
+    type Output = HighlightOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: HighlightArgs,
+    ) -> Result<HighlightOutput, ToolError> {
+        let selectors = args.selectors.required()?;
+        let color = parse_color(&args.color).map_err(ToolError::invalid_argument)?;
+        let mut matched = Vec::new();
+        for selector in selectors {
+            let count = send(
+                sender,
+                |tx| TestMessage::Highlight {
+                    selector: selector.clone(),
+                    color,
+                    label: args.label.clone(),
+                    response: tx,
+                },
+                TIMEOUT,
+            )
+            .await?;
+            matched.push(HighlightMatch {
+                id: selector,
+                matched: count,
+            });
+        }
+        let unmatched: Vec<&str> = matched
+            .iter()
+            .filter(|m| m.matched == 0)
+            .map(|m| m.id.as_str())
+            .collect();
+        if !unmatched.is_empty() {
+            // 已匹配的高亮保留，便于对照截图排查
+            return Err(ToolError::not_found("部分选择器未匹配到元素")
+                .with_details(json!({ "unmatched": unmatched, "highlights": matched })));
         }
+        Ok(HighlightOutput {
+            success: true,
+            highlights: matched,
+            message: "已高亮，调用 clear_highlights 清除".to_string(),
+        })
+    }
+}
+
+struct ClearHighlights;
+
+#[derive(Serialize, JsonSchema)]
+struct ClearHighlightsOutput {
+    success: bool,
+    /// 移除的高亮数量
+    removed: usize,
+}
+
+impl Tool for ClearHighlights {
+    const NAME: &'static str = "clear_highlights";
+    const DESCRIPTION: &'static str = "移除所有高亮";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = NoArgs;
+    type Output = ClearHighlightsOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        _: NoArgs,
+    ) -> Result<ClearHighlightsOutput, ToolError> {
+        let removed = send(
+            sender,
+            |tx| TestMessage::ClearHighlights { response: tx },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(ClearHighlightsOutput {
+            success: true,
+            removed,
+        })
+    }
+}
 
-        _ => return None,
+struct VirtualCursor;
+
+#[derive(Deserialize, JsonSchema)]
+struct VirtualCursorArgs {
+    /// 是否开启
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, JsonSchema)]
+struct VirtualCursorOutput {
+    success: bool,
+    /// 虚拟光标当前是否开启
+    enabled: bool,
+}
+
+impl Tool for VirtualCursor {
+    const NAME: &'static str = "virtual_cursor";
+    const DESCRIPTION: &'static str = "开启或关闭虚拟光标：在最近一次 hover / click / drag 的位置绘制指针，点击时播放按下波纹，截图与录制中可以看出交互顺序。也可以用 --virtual-cursor 启动游戏开启";
+    const STAGE: Option<TestStage> = Some(TestStage::Interaction);
+    type Args = VirtualCursorArgs;
+    type Output = VirtualCursorOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: VirtualCursorArgs,
+    ) -> Result<VirtualCursorOutput, ToolError> {
+        let enabled = send(
+            sender,
+            |tx| TestMessage::SetVirtualCursor {
+                enabled: args.enabled,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await?;
+        Ok(VirtualCursorOutput {
+            success: true,
+            enabled,
+        })
+    }
+}
+
+/// 解析颜色：`#rrggbb`、`r,g,b`（sRGB 0-1）或常用颜色名
+fn parse_color(color: &str) -> Result<[f32; 3], String> {
+    let invalid = || format!("无效的颜色: {}", color);
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() != 6 {
+            return Err(invalid());
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map(|v| v as f32 / 255.0)
+                .map_err(|_| invalid())
+        };
+        return Ok([channel(0)?, channel(2)?, channel(4)?]);
+    }
+    if color.contains(',') {
+        let parts: Vec<f32> = color
+            .split(',')
+            .map(|v| v.trim().parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        return match parts[..] {
+            [r, g, b] => Ok([r, g, b]),
+            _ => Err(invalid()),
+        };
+    }
+    Ok(match color {
+        "red" => [1.0, 0.0, 0.0],
+        "green" => [0.0, 0.8, 0.0],
+        "blue" => [0.0, 0.4, 1.0],
+        "yellow" => [1.0, 0.85, 0.0],
+        "magenta" => [1.0, 0.0, 1.0],
+        "cyan" => [0.0, 0.9, 0.9],
+        "orange" => [1.0, 0.5, 0.0],
+        "white" => [1.0, 1.0, 1.0],
+        _ => return Err(invalid()),
     })
 }
+
+/// 标注图例：编号 → 节点。id 优先取 testId，否则为 uid
+fn legend_json(marks: &[AnnotationMark]) -> Value {
+    marks
+        .iter()
+        .map(|m| {
+            json!({
+                "mark": m.number,
+                "id": m.test_id.clone().unwrap_or_else(|| m.uid.clone()),
+                "uid": m.uid,
+                "test_id": m.test_id,
+                "name": m.name,
+                "text": m.text,
+                "x": m.x,
+                "y": m.y,
+                "width": m.width,
+                "height": m.height,
+            })
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// 稳定截图结果的说明文字
+fn stability_note(stability: Option<Stability>) -> String {
+    match stability {
+        Some(Stability {
+            stable: true,
+            frames_captured,
+        }) => format!("，画面已稳定（比较 {} 帧）", frames_captured),
+        Some(Stability {
+            stable: false,
+            frames_captured,
+        }) => format!(
+            "，等待稳定超时（比较 {} 帧），返回最后一帧",
+            frames_captured
+        ),
+        None => String::new(),
+    }
+}
+
+/// 未指定 path 时的截图保存路径（与测试日志同目录下的 screenshots/）
+fn default_screenshot_path(extension: &str) -> String {
+    artifact_dir("screenshots")
+        .join(format!(
+            "screenshot-{}.{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"),
+            extension
+        ))
+        .to_string_lossy()
+        .to_string()
+}
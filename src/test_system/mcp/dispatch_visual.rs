@@ -0,0 +1,724 @@
+//! 视觉工具：compare_screenshot 视觉回归，probe_pixels 像素探测，start_recording / stop_recording 场景录制
+
+use bevy::math::{Rect, Vec2};
+use crossbeam_channel::Sender;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::test_system::channel::{
+    ColorStats, ImageEncoding, ProbeResult, ProbeTarget, RecordingFormat, RecordingOptions,
+    ScreenshotOptions, TestMessage, TestStage,
+};
+use crate::test_system::screenshot::write_file;
+use crate::test_system::visual_diff::{self, DiffMetric, DiffOptions, MaskRect};
+
+use super::super::context;
+use super::super::tools::{Tool, ToolError, ToolRegistry};
+use super::dispatch_shared::{
+    artifact_dir, expand_fingerprint, send, SelectorArgs, SCREENSHOT_TIMEOUT, TIMEOUT,
+};
+
+/// 默认基准图目录，可用 VISUAL_BASELINE_DIR 环境变量覆盖
+const DEFAULT_BASELINE_DIR: &str = "tests/baselines";
+
+pub fn register(registry: &mut ToolRegistry) {
+    registry
+        .register::<CompareScreenshot>()
+        .register::<ProbePixels>()
+        .register::<StartRecording>()
+        .register::<StopRecording>();
+}
+
+/// 物理像素矩形
+#[derive(Deserialize, JsonSchema)]
+struct RectArg {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+/// 忽略区域：TestId 字符串或整帧物理像素矩形
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum MaskArg {
+    TestId(String),
+    Rect(RectArg),
+}
+
+#[derive(Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum MetricArg {
+    #[default]
+    Pixel,
+    Ssim,
+}
+
+struct CompareScreenshot;
+
+#[derive(Deserialize, JsonSchema)]
+struct CompareScreenshotArgs {
+    /// 基准图名称，对应 {baseline_dir}/{name}.png
+    name: String,
+    /// 只对比该元素区域（可选，语法同 element_screenshot）
+    id: Option<String>,
+    /// 元素四周留白（逻辑像素）
+    #[serde(default)]
+    #[schemars(range(min = 0))]
+    padding: f32,
+    /// 单通道允许的最大差值
+    #[serde(default = "default_tolerance")]
+    tolerance: u8,
+    /// 允许的差异像素占比（metric=pixel 时的判定依据）
+    #[serde(default = "default_max_diff_ratio")]
+    #[schemars(range(min = 0, max = 1))]
+    max_diff_ratio: f64,
+    /// 判定方式：pixel（默认）按差异像素占比，ssim 按平均结构相似度（对抗锯齿差异不敏感）
+    #[serde(default)]
+    metric: MetricArg,
+    /// metric=ssim 时的通过阈值
+    #[serde(default = "default_ssim_threshold")]
+    #[schemars(range(min = 0, max = 1))]
+    ssim_threshold: f64,
+    /// 忽略区域：TestId 字符串（经 UI 快照解析为节点矩形，匹配所有同名节点）或整帧物理像素矩形
+    #[serde(default)]
+    mask: Vec<MaskArg>,
+    /// 基准图目录，默认 tests/baselines 或 VISUAL_BASELINE_DIR；{fingerprint} 会替换为渲染环境指纹
+    baseline_dir: Option<String>,
+}
+
+fn default_tolerance() -> u8 {
+    DiffOptions::default().tolerance
+}
+
+fn default_max_diff_ratio() -> f64 {
+    DiffOptions::default().max_diff_ratio
+}
+
+fn default_ssim_threshold() -> f64 {
+    DiffOptions::default().ssim_threshold
+}
+
+impl Tool for CompareScreenshot {
+    const NAME: &'static str = "compare_screenshot";
+    const DESCRIPTION: &'static str = "截图并与基准 PNG 对比（视觉回归）。返回 passed、差异像素数与占比；失败时附带高亮差异图，并把 actual/diff 图写入测试日志目录下的 visual/。以 --update-baselines 启动游戏时改为写入新的基准图";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = CompareScreenshotArgs;
+    type Output = Value;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: CompareScreenshotArgs,
+    ) -> Result<Value, ToolError> {
+        let name = args.name;
+        if name.is_empty() || name.contains("..") || Path::new(&name).is_absolute() {
+            return Err(ToolError::invalid_argument(format!(
+                "无效的基准图名称: {}",
+                name
+            )));
+        }
+        let options = DiffOptions {
+            tolerance: args.tolerance,
+            max_diff_ratio: args.max_diff_ratio,
+            metric: match args.metric {
+                MetricArg::Pixel => DiffMetric::Pixel,
+                MetricArg::Ssim => DiffMetric::Ssim,
+            },
+            ssim_threshold: args.ssim_threshold,
+        };
+        let masks = resolve_masks(sender, &args.mask).await?;
+        let baseline_dir = args.baseline_dir.unwrap_or_else(baseline_dir);
+        let baseline_dir = PathBuf::from(expand_fingerprint(sender, baseline_dir).await?);
+
+        let (png, origin) = capture_png(sender, args.id.as_deref(), args.padding).await?;
+
+        context::report("截图完成，正在与基准图对比");
+        let job = CompareJob {
+            png,
+            baseline: baseline_dir.join(format!("{}.png", name)),
+            output_dir: artifact_dir("visual"),
+            name,
+            options,
+            masks: offset_masks(&masks, origin),
+            update: update_baselines_mode(),
+        };
+        let (verdict, diff_png) = tokio::task::spawn_blocking(move || job.run())
+            .await
+            .map_err(|e| ToolError::internal(format!("对比任务失败: {}", e)))??;
+
+        let text = serde_json::to_string_pretty(&verdict).unwrap_or_default();
+        match diff_png {
+            // 对比失败时附带高亮差异图
+            Some(diff_png) => {
+                use base64::Engine;
+                Ok(json!({
+                    "__mcp_image": {
+                        "data": base64::engine::general_purpose::STANDARD.encode(&diff_png),
+                        "mime_type": "image/png"
+                    },
+                    "text": text
+                }))
+            }
+            None => Ok(verdict),
+        }
+    }
+}
+
+/// 采样点：{x, y} 或 [x, y]
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum PointArg {
+    Object { x: f32, y: f32 },
+    Pair([f32; 2]),
+}
+
+struct ProbePixels;
+
+#[derive(Serialize, JsonSchema)]
+struct ProbeOutput {
+    success: bool,
+    /// 各采样点的颜色
+    points: Vec<PointColor>,
+    /// 各区域（rect / rects / id / ids）的颜色统计
+    regions: Vec<RegionColor>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct PointColor {
+    x: u32,
+    y: u32,
+    color: ColorOutput,
+}
+
+/// 区域颜色统计，坐标为实际采样区域（已限制在画面内）
+#[derive(Serialize, JsonSchema)]
+struct RegionColor {
+    /// 区域来自选择器时为该选择器
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    pixels: u32,
+    avg: ColorOutput,
+    min: ColorOutput,
+    max: ColorOutput,
+    /// 逐通道中位数，不受按钮文字等少量异色像素影响
+    median: ColorOutput,
+}
+
+/// sRGB 颜色
+#[derive(Serialize, JsonSchema)]
+struct ColorOutput {
+    /// 8 位通道值（平均值可能带小数）
+    rgb: [f32; 3],
+    /// 0-1 通道值，与 Color::srgb 一致
+    srgb: [f32; 3],
+    /// #rrggbb
+    hex: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ProbePixelsArgs {
+    /// 采样点：{x, y} 或 [x, y]
+    #[serde(default)]
+    points: Vec<PointArg>,
+    /// 统计该矩形区域
+    rect: Option<RectArg>,
+    /// 统计多个矩形区域（给出时忽略 rect）
+    rects: Option<Vec<RectArg>>,
+    #[serde(flatten)]
+    selectors: SelectorArgs,
+}
+
+impl Tool for ProbePixels {
+    const NAME: &'static str = "probe_pixels";
+    const DESCRIPTION: &'static str = "截取一帧并读取像素颜色（sRGB）：points 返回各点颜色，rect / id 返回区域的 avg / min / max / median。坐标为物理像素，与截图一致";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = ProbePixelsArgs;
+    type Output = ProbeOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: ProbePixelsArgs,
+    ) -> Result<ProbeOutput, ToolError> {
+        let targets = probe_targets(args)?;
+        let results = send(
+            sender,
+            |tx| TestMessage::ProbePixels {
+                targets,
+                response: tx,
+            },
+            SCREENSHOT_TIMEOUT,
+        )
+        .await??;
+        let (points, regions): (Vec<_>, Vec<_>) = results
+            .iter()
+            .partition(|r| matches!(r.target, ProbeTarget::Point { .. }));
+        Ok(ProbeOutput {
+            success: true,
+            points: points
+                .into_iter()
+                .map(|r| PointColor {
+                    x: r.x,
+                    y: r.y,
+                    color: color_output(r.stats.avg),
+                })
+                .collect(),
+            regions: regions.into_iter().map(region_color).collect(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum RecordingFormatArg {
+    #[default]
+    Apng,
+    Gif,
+}
+
+struct StartRecording;
+
+#[derive(Deserialize, JsonSchema)]
+struct StartRecordingArgs {
+    /// 每 N 帧截取一次
+    #[serde(default = "default_every_n_frames")]
+    #[schemars(range(min = 1))]
+    every_n_frames: u32,
+    /// 录制格式，默认 apng
+    #[serde(default)]
+    format: RecordingFormatArg,
+    /// 缩放比例
+    #[serde(default = "default_recording_scale")]
+    #[schemars(range(max = 1), extend("exclusiveMinimum" = 0))]
+    scale: f32,
+    /// 最大宽度（像素）
+    #[schemars(range(min = 1))]
+    max_width: Option<u32>,
+    /// 内存中最多保留的帧数，超出时丢弃最早的帧
+    #[serde(default = "default_max_frames")]
+    #[schemars(range(min = 1))]
+    max_frames: u32,
+}
+
+fn default_every_n_frames() -> u32 {
+    2
+}
+
+fn default_recording_scale() -> f32 {
+    0.5
+}
+
+fn default_max_frames() -> u32 {
+    600
+}
+
+#[derive(Serialize, JsonSchema)]
+struct StartRecordingOutput {
+    success: bool,
+    /// 录制文件扩展名：png（APNG）或 gif
+    format: String,
+    message: String,
+}
+
+impl Tool for StartRecording {
+    const NAME: &'static str = "start_recording";
+    const DESCRIPTION: &'static str = "开始录制：每 N 帧截取一次画面到内存，stop_recording 时编码为 APNG 或 GIF。同一时间只能有一个录制";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = StartRecordingArgs;
+    type Output = StartRecordingOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: StartRecordingArgs,
+    ) -> Result<StartRecordingOutput, ToolError> {
+        let options = RecordingOptions {
+            every_n_frames: args.every_n_frames,
+            format: match args.format {
+                RecordingFormatArg::Apng => RecordingFormat::Apng,
+                RecordingFormatArg::Gif => RecordingFormat::Gif,
+            },
+            scale: Some(args.scale.clamp(0.05, 1.0)),
+            max_width: args.max_width,
+            max_frames: args.max_frames as usize,
+        };
+        let format = options.format;
+        send(
+            sender,
+            |tx| TestMessage::StartRecording {
+                options,
+                response: tx,
+            },
+            TIMEOUT,
+        )
+        .await?
+        .map_err(|e| ToolError::new("already_recording", e))?;
+        Ok(StartRecordingOutput {
+            success: true,
+            format: format.extension().to_string(),
+            message: "录制已开始，调用 stop_recording 结束并保存".to_string(),
+        })
+    }
+}
+
+struct StopRecording;
+
+#[derive(Deserialize, JsonSchema)]
+struct StopRecordingArgs {
+    /// 保存路径（可选），不含扩展名时按录制格式补全
+    path: Option<String>,
+    /// 未指定 path 时的文件名
+    #[serde(default = "default_recording_name")]
+    name: String,
+    /// 丢弃录制，不写入文件
+    #[serde(default)]
+    discard: bool,
+}
+
+fn default_recording_name() -> String {
+    "recording".to_string()
+}
+
+#[derive(Serialize, JsonSchema)]
+struct StopRecordingOutput {
+    success: bool,
+    /// 写入的文件（discard 时为 null）
+    path: Option<String>,
+    frames: u32,
+    /// 因超过 max_frames 被丢弃的最早帧数
+    dropped_frames: u32,
+    width: u32,
+    height: u32,
+    duration_ms: u64,
+    /// 文件大小（字节）
+    bytes: usize,
+}
+
+impl Tool for StopRecording {
+    const NAME: &'static str = "stop_recording";
+    const DESCRIPTION: &'static str = "停止录制并写入文件（默认为测试日志目录下的 recording.png / recording.gif），返回帧数、时长与路径";
+    const STAGE: Option<TestStage> = Some(TestStage::Query);
+    type Args = StopRecordingArgs;
+    type Output = StopRecordingOutput;
+
+    async fn call(
+        sender: &Sender<TestMessage>,
+        args: StopRecordingArgs,
+    ) -> Result<StopRecordingOutput, ToolError> {
+        // 未指定 path 时写入场景目录，扩展名由录制格式决定
+        let path = if args.discard {
+            None
+        } else {
+            Some(args.path.unwrap_or_else(|| {
+                artifact_dir("")
+                    .join(&args.name)
+                    .to_string_lossy()
+                    .to_string()
+            }))
+        };
+        let summary = send(
+            sender,
+            |tx| TestMessage::StopRecording { path, response: tx },
+            SCREENSHOT_TIMEOUT * 6,
+        )
+        .await??;
+        Ok(StopRecordingOutput {
+            success: true,
+            path: summary.path,
+            frames: summary.frames,
+            dropped_frames: summary.dropped,
+            width: summary.width,
+            height: summary.height,
+            duration_ms: summary.duration_ms,
+            bytes: summary.bytes,
+        })
+    }
+}
+
+/// 基准图目录：VISUAL_BASELINE_DIR 或默认值
+fn baseline_dir() -> String {
+    std::env::var("VISUAL_BASELINE_DIR").unwrap_or_else(|_| DEFAULT_BASELINE_DIR.to_string())
+}
+
+/// 以 --update-baselines 启动时，对比改为写入新的基准图
+pub fn update_baselines_mode() -> bool {
+    std::env::args().any(|arg| arg == "--update-baselines")
+}
+
+/// 探测目标：points、rect / rects 与 id / ids
+fn probe_targets(args: ProbePixelsArgs) -> Result<Vec<ProbeTarget>, ToolError> {
+    let mut targets: Vec<ProbeTarget> = args
+        .points
+        .into_iter()
+        .map(|point| match point {
+            PointArg::Object { x, y } | PointArg::Pair([x, y]) => ProbeTarget::Point { x, y },
+        })
+        .collect();
+    let rects = args
+        .rects
+        .unwrap_or_else(|| args.rect.into_iter().collect());
+    targets.extend(rects.into_iter().map(|rect| ProbeTarget::Rect {
+        x: rect.x,
+        y: rect.y,
+        width: rect.width,
+        height: rect.height,
+    }));
+    targets.extend(args.selectors.list().into_iter().map(ProbeTarget::Element));
+    if targets.is_empty() {
+        return Err(ToolError::invalid_argument(
+            "至少需要 points、rect / rects 或 id / ids 之一",
+        ));
+    }
+    Ok(targets)
+}
+
+/// 颜色的三种表示：8 位 rgb、0-1 sRGB 与十六进制
+fn color_output(rgb: [f32; 3]) -> ColorOutput {
+    let round = |v: f32| (v * 1000.0).round() / 1000.0;
+    ColorOutput {
+        rgb: rgb.map(round),
+        srgb: rgb.map(|v| round(v / 255.0)),
+        hex: format!(
+            "#{:02x}{:02x}{:02x}",
+            rgb[0].round() as u8,
+            rgb[1].round() as u8,
+            rgb[2].round() as u8
+        ),
+    }
+}
+
+fn region_color(result: &ProbeResult) -> RegionColor {
+    let ColorStats {
+        avg,
+        min,
+        max,
+        median,
+        pixels,
+    } = result.stats;
+    let as_f32 = |c: [u8; 3]| c.map(f32::from);
+    RegionColor {
+        id: match &result.target {
+            ProbeTarget::Element(id) => Some(id.clone()),
+            _ => None,
+        },
+        x: result.x,
+        y: result.y,
+        width: result.width,
+        height: result.height,
+        pixels,
+        avg: color_output(avg),
+        min: color_output(as_f32(min)),
+        max: color_output(as_f32(max)),
+        median: color_output(as_f32(median)),
+    }
+}
+
+/// 解析忽略区域：`{x, y, width, height}` 矩形（整帧物理像素）或 TestId 字符串。
+/// TestId 通过 UI 快照解析为节点矩形，可匹配多个节点（如所有 "ball"）
+async fn resolve_masks(
+    sender: &Sender<TestMessage>,
+    masks: &[MaskArg],
+) -> Result<Vec<Rect>, ToolError> {
+    let mut rects = Vec::new();
+    let mut test_ids = Vec::new();
+    for mask in masks {
+        match mask {
+            MaskArg::TestId(id) => test_ids.push(id),
+            MaskArg::Rect(r) => rects.push(Rect::new(r.x, r.y, r.x + r.width, r.y + r.height)),
+        }
+    }
+    if !test_ids.is_empty() {
+        let nodes = send(
+            sender,
+            |tx| TestMessage::TakeSnapshot { response: tx },
+            TIMEOUT,
+        )
+        .await?;
+        for id in test_ids {
+            let matched: Vec<Rect> = nodes
+                .iter()
+                .filter(|n| n.test_id.as_deref() == Some(id.as_str()))
+                .filter_map(|n| n.rect_px)
+                .collect();
+            if matched.is_empty() {
+                return Err(ToolError::not_found(format!("mask 未匹配到节点: {}", id))
+                    .with_details(json!({ "mask": id })));
+            }
+            rects.extend(matched);
+        }
+    }
+    Ok(rects)
+}
+
+/// 把整帧坐标的忽略区域换算到截图（可能是元素裁剪）坐标
+fn offset_masks(rects: &[Rect], origin: (u32, u32)) -> Vec<MaskRect> {
+    let origin = Vec2::new(origin.0 as f32, origin.1 as f32);
+    rects
+        .iter()
+        .filter_map(|rect| {
+            let min = (rect.min - origin).max(Vec2::ZERO).floor();
+            let max = (rect.max - origin).ceil();
+            (max.x > min.x && max.y > min.y).then_some(MaskRect {
+                x: min.x as u32,
+                y: min.y as u32,
+                width: (max.x - min.x) as u32,
+                height: (max.y - min.y) as u32,
+            })
+        })
+        .collect()
+}
+
+/// 截取 PNG：指定 id 时裁剪该元素，否则为整帧。返回 PNG 与截图左上角在整帧中的坐标
+async fn capture_png(
+    sender: &Sender<TestMessage>,
+    id: Option<&str>,
+    padding: f32,
+) -> Result<(Vec<u8>, (u32, u32)), ToolError> {
+    if let Some(id) = id {
+        let mut elements = send(
+            sender,
+            |tx| TestMessage::ElementScreenshot {
+                selectors: vec![id.to_string()],
+                padding,
+                encoding: ImageEncoding::Png,
+                response: tx,
+            },
+            SCREENSHOT_TIMEOUT,
+        )
+        .await??;
+        if elements.is_empty() {
+            return Err(ToolError::not_found(format!("未找到元素: {}", id)));
+        }
+        let element = elements.swap_remove(0);
+        return Ok((element.image.bytes, (element.x, element.y)));
+    }
+    let image = send(
+        sender,
+        |tx| TestMessage::Screenshot {
+            options: ScreenshotOptions::default(),
+            response: tx,
+        },
+        SCREENSHOT_TIMEOUT,
+    )
+    .await??;
+    Ok((image.bytes, (0, 0)))
+}
+
+/// 在阻塞线程中执行的对比任务
+struct CompareJob {
+    png: Vec<u8>,
+    baseline: PathBuf,
+    output_dir: PathBuf,
+    name: String,
+    options: DiffOptions,
+    masks: Vec<MaskRect>,
+    update: bool,
+}
+
+impl CompareJob {
+    /// 返回 (结果 JSON, 失败时的差异图 PNG)
+    fn run(self) -> Result<(Value, Option<Vec<u8>>), ToolError> {
+        let baseline_path = self.baseline.to_string_lossy().to_string();
+        if self.update {
+            write_file(&baseline_path, &self.png)?;
+            return Ok((
+                json!({
+                    "success": true,
+                    "passed": true,
+                    "status": "updated",
+                    "baseline": baseline_path,
+                    "message": format!("已更新基准图: {}", baseline_path)
+                }),
+                None,
+            ));
+        }
+
+        let actual = image::load_from_memory(&self.png)
+            .map_err(|e| ToolError::internal(format!("解码截图失败: {}", e)))?
+            .to_rgb8();
+        let actual_path = self.output_path("actual");
+        if !self.baseline.exists() {
+            write_file(&actual_path, &self.png)?;
+            return Ok((
+                json!({
+                    "success": false,
+                    "passed": false,
+                    "status": "baseline_missing",
+                    "baseline": baseline_path,
+                    "actual": actual_path,
+                    "message": format!("基准图不存在: {}（可用 --update-baselines 生成）", baseline_path)
+                }),
+                None,
+            ));
+        }
+        let baseline = image::open(&self.baseline)
+            .map_err(|e| ToolError::internal(format!("读取基准图失败 {}: {}", baseline_path, e)))?
+            .to_rgb8();
+
+        let result = match visual_diff::compare(&actual, &baseline, self.options, &self.masks) {
+            Ok(result) => result,
+            Err(message) => {
+                write_file(&actual_path, &self.png)?;
+                return Ok((
+                    json!({
+                        "success": false,
+                        "passed": false,
+                        "status": "size_mismatch",
+                        "baseline": baseline_path,
+                        "actual": actual_path,
+                        "message": message
+                    }),
+                    None,
+                ));
+            }
+        };
+
+        let mut verdict = json!({
+            "success": result.passed,
+            "passed": result.passed,
+            "status": if result.passed { "passed" } else { "failed" },
+            "baseline": baseline_path,
+            "metric": match self.options.metric {
+                DiffMetric::Pixel => "pixel",
+                DiffMetric::Ssim => "ssim",
+            },
+            "diff_pixels": result.diff_pixels,
+            "total_pixels": result.total_pixels,
+            "masked_pixels": result.masked_pixels,
+            "diff_ratio": result.diff_ratio,
+            "max_channel_delta": result.max_channel_delta,
+            "ssim": result.ssim,
+            "tolerance": self.options.tolerance,
+            "max_diff_ratio": self.options.max_diff_ratio,
+            "ssim_threshold": self.options.ssim_threshold,
+        });
+        if result.passed {
+            return Ok((verdict, None));
+        }
+
+        let mut diff_png = Vec::new();
+        image::DynamicImage::ImageRgb8(result.diff_image)
+            .write_to(
+                &mut std::io::Cursor::new(&mut diff_png),
+                image::ImageFormat::Png,
+            )
+            .map_err(|e| ToolError::new("encode_failed", format!("编码差异图失败: {}", e)))?;
+        let diff_path = self.output_path("diff");
+        write_file(&actual_path, &self.png)?;
+        write_file(&diff_path, &diff_png)?;
+        verdict["actual"] = json!(actual_path);
+        verdict["diff"] = json!(diff_path);
+        Ok((verdict, Some(diff_png)))
+    }
+
+    fn output_path(&self, kind: &str) -> String {
+        self.output_dir
+            .join(format!("{}.{}.png", self.name, kind))
+            .to_string_lossy()
+            .to_string()
+    }
+}
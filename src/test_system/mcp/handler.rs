@@ -1,86 +1,335 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use crossbeam_channel::Sender;
+use log::info;
 use serde_json::{json, Value};
+use tokio::sync::mpsc;
 
 use crate::test_system::channel::TestMessage;
 
-use super::dispatch::call_tool;
-use super::protocol::{RpcReq, RpcResp};
-use super::tools::tool_list;
+use super::context::{self, CallContext};
+use super::dispatch::{call_tool, tool_list, ToolOutput};
+use super::protocol::{
+    negotiate_version, params_object, RpcBody, RpcReq, RpcResp, INTERNAL_ERROR, INVALID_PARAMS,
+    INVALID_REQUEST, METHOD_NOT_FOUND, PROTOCOL_VERSIONS, RESOURCE_NOT_FOUND,
+};
+use super::stream;
+use super::{prompts, resources};
 
+/// POST /mcp：单条或批量 JSON-RPC 消息。通知不返回响应，只有通知时返回 202；
+/// 客户端接受 text/event-stream 且包含 tools/call 时以 SSE 响应
 pub async fn mcp_handler(
     State(sender): State<Sender<TestMessage>>,
-    Json(req): Json<RpcReq>,
-) -> impl IntoResponse {
-    let id = req.id.clone();
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(resp) = unsupported_protocol_version(&headers) {
+        return resp;
+    }
+    let (messages, batch) = match RpcBody::parse(&body) {
+        Ok(RpcBody::Single(message)) => (vec![message], false),
+        Ok(RpcBody::Batch(messages)) => (messages, true),
+        Err(resp) => return Json(resp).into_response(),
+    };
+    let has_tool_call = messages
+        .iter()
+        .any(|m| matches!(m, Ok(req) if req.method == "tools/call"));
+    if has_tool_call && accepts_event_stream(&headers) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for message in messages {
+                if let Some(resp) = handle_message(&sender, message, Some(&tx)).await {
+                    let _ = tx.send(serde_json::to_value(resp).unwrap_or_default());
+                }
+            }
+            // 发送端释放后 SSE 流结束
+        });
+        return stream::sse(rx, None).into_response();
+    }
+
+    let mut responses = Vec::new();
+    for message in messages {
+        responses.extend(handle_message(&sender, message, None).await);
+    }
+    if responses.is_empty() {
+        StatusCode::ACCEPTED.into_response()
+    } else if batch {
+        Json(responses).into_response()
+    } else {
+        Json(responses.remove(0)).into_response()
+    }
+}
+
+/// GET /mcp：打开接收服务端通知的 SSE 流
+pub async fn mcp_stream_handler(headers: HeaderMap) -> Response {
+    if let Some(resp) = unsupported_protocol_version(&headers) {
+        return resp;
+    }
+    if !accepts_event_stream(&headers) {
+        return StatusCode::NOT_ACCEPTABLE.into_response();
+    }
+    let (tx, rx) = mpsc::unbounded_channel();
+    let guard = stream::open(tx);
+    stream::sse(rx, Some(guard)).into_response()
+}
+
+/// 初始化之后客户端在 MCP-Protocol-Version 头中带上协商的版本，不支持的版本返回 400；
+/// 没有该头（2024-11-05 的客户端）时照常处理
+fn unsupported_protocol_version(headers: &HeaderMap) -> Option<Response> {
+    let version = headers
+        .get("mcp-protocol-version")?
+        .to_str()
+        .unwrap_or_default();
+    if PROTOCOL_VERSIONS.contains(&version) {
+        return None;
+    }
+    let resp = RpcResp::err(
+        Value::Null,
+        INVALID_REQUEST,
+        format!(
+            "不支持的协议版本: {}（支持 {}）",
+            version,
+            PROTOCOL_VERSIONS.join(", ")
+        ),
+    );
+    Some((StatusCode::BAD_REQUEST, Json(resp)).into_response())
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains("text/event-stream"))
+}
+
+/// 处理单条消息，通知返回 None；HTTP 与 stdio 传输共用。
+/// replies 是该消息所在请求的输出流（POST 的 SSE 响应或 stdio），调用期间的进度通知发到这里
+pub(super) async fn handle_message(
+    sender: &Sender<TestMessage>,
+    message: Result<RpcReq, RpcResp>,
+    replies: Option<&mpsc::UnboundedSender<Value>>,
+) -> Option<RpcResp> {
+    let req = match message {
+        Ok(req) => req,
+        Err(resp) => return Some(resp),
+    };
+    match req.method.as_str() {
+        "notifications/cancelled" => {
+            let params = params_object(&req);
+            if let Some(request_id) = params.and_then(|p| p.get("requestId")) {
+                let reason = params.and_then(|p| p.get("reason")).and_then(Value::as_str);
+                // 调用可能已经结束，找不到时忽略
+                context::cancel(request_id, reason);
+            }
+            return None;
+        }
+        "tools/call" => {
+            let resp = handle_tool_call(sender, &req, replies).await;
+            return resp.filter(|_| !req.is_notification());
+        }
+        _ => {}
+    }
+    if !req.is_notification() {
+        return Some(handle_request(sender, req).await);
+    }
+    // 不带 id 的普通方法照常执行，只是不返回结果
+    if !req.method.starts_with("notifications/") {
+        handle_request(sender, req).await;
+    }
+    None
+}
+
+async fn handle_request(sender: &Sender<TestMessage>, req: RpcReq) -> RpcResp {
+    let id = req.id.clone().unwrap_or(Value::Null);
 
     match req.method.as_str() {
-        "initialize" => Json(RpcResp::ok(
+        "initialize" => RpcResp::ok(
             id,
             json!({
-                "protocolVersion": "2024-11-05",
-                "capabilities": { "tools": {} },
+                "protocolVersion": negotiate_version(
+                    params_object(&req)
+                        .and_then(|p| p.get("protocolVersion"))
+                        .and_then(Value::as_str)
+                ),
+                "capabilities": {
+                    "tools": { "listChanged": true },
+                    "logging": {},
+                    "prompts": {},
+                    "resources": { "subscribe": true, "listChanged": true }
+                },
                 "serverInfo": { "name": "bevy-game", "version": "1.0.0" }
             }),
-        ))
-        .into_response(),
+        ),
 
-        "notifications/initialized" => StatusCode::NO_CONTENT.into_response(),
+        "tools/list" => RpcResp::ok(id, tool_list()),
 
-        "tools/list" => Json(RpcResp::ok(id, tool_list())).into_response(),
+        "resources/list" => RpcResp::ok(id, resources::list()),
 
-        "tools/call" => {
-            let params = req.params.as_ref().and_then(|v| v.as_object());
+        "resources/templates/list" => RpcResp::ok(id, resources::templates()),
+
+        "resources/read" => {
+            let Some(uri) = params_object(&req)
+                .and_then(|p| p.get("uri"))
+                .and_then(Value::as_str)
+            else {
+                return RpcResp::err(id, INVALID_PARAMS, "缺少参数: uri");
+            };
+            match resources::read(sender, uri).await {
+                Ok(result) => RpcResp::ok(id, result),
+                Err(None) => RpcResp::err(id, RESOURCE_NOT_FOUND, format!("资源不存在: {}", uri)),
+                Err(Some(e)) => RpcResp::err(id, INTERNAL_ERROR, e),
+            }
+        }
+
+        method @ ("resources/subscribe" | "resources/unsubscribe") => {
+            let Some(uri) = params_object(&req)
+                .and_then(|p| p.get("uri"))
+                .and_then(Value::as_str)
+            else {
+                return RpcResp::err(id, INVALID_PARAMS, "缺少参数: uri");
+            };
+            if method == "resources/unsubscribe" {
+                resources::unsubscribe(uri);
+            } else if resources::exists(uri) {
+                resources::subscribe(uri);
+            } else {
+                return RpcResp::err(id, RESOURCE_NOT_FOUND, format!("资源不存在: {}", uri));
+            }
+            RpcResp::ok(id, json!({}))
+        }
+
+        "prompts/list" => RpcResp::ok(id, prompts::list()),
+
+        "prompts/get" => {
+            let params = params_object(&req);
             let name = params
-                .and_then(|m| m.get("name"))
+                .and_then(|p| p.get("name"))
                 .and_then(Value::as_str)
                 .unwrap_or("");
-            let args = params
-                .and_then(|m| m.get("arguments"))
+            let arguments = params
+                .and_then(|p| p.get("arguments"))
+                .and_then(Value::as_object)
                 .cloned()
-                .unwrap_or(json!({}));
-
-            let resp = match call_tool(&sender, name, &args).await {
-                Ok(data) => {
-                    // 对标 Chrome DevTools MCP attachImage：
-                    // dispatch_ui.rs 截图成功后在 Value 里放 __mcp_image 标记，
-                    // 这里检测到后输出标准 MCP ImageContent { type:"image", data, mimeType }
-                    if let Some(img) = data.get("__mcp_image") {
-                        let img_data = img.get("data").and_then(Value::as_str).unwrap_or("");
-                        let mime = img
-                            .get("mimeType")
-                            .and_then(Value::as_str)
-                            .unwrap_or("image/png");
-                        let text = data
-                            .get("text")
-                            .and_then(Value::as_str)
-                            .unwrap_or("截图完成");
-                        RpcResp::ok(
-                            id,
-                            json!({
-                                "content": [
-                                    { "type": "text", "text": text },
-                                    { "type": "image", "data": img_data, "mimeType": mime }
-                                ]
-                            }),
-                        )
-                    } else {
-                        RpcResp::ok(
-                            id,
-                            json!({
-                                "content": [{
-                                    "type": "text",
-                                    "text": serde_json::to_string_pretty(&data).unwrap_or_default()
-                                }]
-                            }),
-                        )
-                    }
-                }
-                Err(e) => RpcResp::err(id, -32603, e),
-            };
-            Json(resp).into_response()
+                .unwrap_or_default();
+            match prompts::get(sender, name, &arguments).await {
+                Ok(result) => RpcResp::ok(id, result),
+                Err((code, e)) => RpcResp::err(id, code, e),
+            }
         }
 
-        unknown => Json(RpcResp::err(id, -32601, format!("未知方法: {}", unknown))).into_response(),
+        "logging/setLevel" => {
+            let level = params_object(&req)
+                .and_then(|p| p.get("level"))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            match stream::set_log_level(level) {
+                Ok(()) => RpcResp::ok(id, json!({})),
+                Err(e) => RpcResp::err(id, INVALID_PARAMS, e),
+            }
+        }
+
+        unknown => RpcResp::err(id, METHOD_NOT_FOUND, format!("未知方法: {}", unknown)),
     }
 }
+
+/// tools/call：在调用上下文（进度、取消、timeout_ms）中执行工具；被取消时返回 None
+async fn handle_tool_call(
+    sender: &Sender<TestMessage>,
+    req: &RpcReq,
+    replies: Option<&mpsc::UnboundedSender<Value>>,
+) -> Option<RpcResp> {
+    let id = req.id.clone().unwrap_or(Value::Null);
+    let params = params_object(req);
+    let name = params
+        .and_then(|m| m.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let args = params
+        .and_then(|m| m.get("arguments"))
+        .cloned()
+        .unwrap_or(json!({}));
+
+    let progress_token = params
+        .and_then(|m| m.get("_meta"))
+        .and_then(|m| m.get("progressToken"))
+        .cloned();
+    let timeout_ms = args.get("timeout_ms").and_then(Value::as_u64);
+    let (context, _guard) = CallContext::new(
+        req.id.as_ref(),
+        progress_token,
+        timeout_ms,
+        replies.cloned(),
+    );
+
+    let result = match context.scope(call_tool(sender, name, &args)).await {
+        Ok(ToolOutput { data, frame }) => {
+            let meta = json!({ "frame": frame });
+            // 对标 Chrome DevTools MCP attachImage：
+            // dispatch_ui.rs 截图成功后在 Value 里放 __mcp_image 标记（单张或数组），
+            // 这里检测到后输出标准 MCP ImageContent { type:"image", data, mimeType }
+            if let Some(img) = data.get("__mcp_image") {
+                let text = data
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .unwrap_or("截图完成");
+                let mut content = vec![json!({ "type": "text", "text": text })];
+                let images = match img {
+                    Value::Array(list) => list.iter().collect(),
+                    single => vec![single],
+                };
+                for img in images {
+                    let img_data = img.get("data").and_then(Value::as_str).unwrap_or("");
+                    let mime = img
+                        .get("mime_type")
+                        .and_then(Value::as_str)
+                        .unwrap_or("image/png");
+                    content.push(json!({ "type": "image", "data": img_data, "mimeType": mime }));
+                }
+                RpcResp::ok(id, json!({ "content": content, "_meta": meta }))
+            } else {
+                // 对象结果同时作为 structuredContent 返回（工具声明了 outputSchema 时与之一致），
+                // 文本内容保留给不支持结构化结果的旧客户端
+                let mut result = json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&data).unwrap_or_default()
+                    }],
+                    "_meta": meta
+                });
+                if data.is_object() {
+                    result["structuredContent"] = data;
+                }
+                RpcResp::ok(id, result)
+            }
+        }
+        // 未知工具是协议层错误；其余失败作为工具结果返回，客户端（模型）可以看到并据此调整
+        Err(e) if e.code == "unknown_tool" => RpcResp::err(id, INVALID_PARAMS, e.message),
+        // 被取消的请求不再响应
+        Err(e) if e.code == "cancelled" => {
+            info!("工具调用已取消: {} ({})", name, e.message);
+            return None;
+        }
+        Err(e) => {
+            // 错误对象同时作为 structuredContent 返回，客户端不必从文本中解析
+            let error = json!({ "error": e.to_json() });
+            RpcResp::ok(
+                id,
+                json!({
+                    "content": [{
+                        "type": "text",
+                        "text": serde_json::to_string_pretty(&error).unwrap_or_default()
+                    }],
+                    "structuredContent": error,
+                    "isError": true
+                }),
+            )
+        }
+    };
+    Some(result)
+}
@@ -1,6 +1,16 @@
+mod context;
 mod dispatch;
 mod handler;
+mod prompts;
 mod protocol;
+mod resources;
+mod schema;
+mod stdio;
+mod stream;
 mod tools;
 
-pub use handler::mcp_handler;
+pub use dispatch::register_custom_tool;
+pub use handler::{mcp_handler, mcp_stream_handler};
+pub use resources::watch as watch_resources;
+pub use stdio::serve as serve_stdio;
+pub use stream::forward_log;
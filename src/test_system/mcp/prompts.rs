@@ -0,0 +1,167 @@
+//! MCP 提示模板：常见测试工作流，预先填入当前 UI 快照与最近的游戏日志
+
+use crossbeam_channel::Sender;
+use serde_json::{json, Map, Value};
+
+use crate::test_system::channel::TestMessage;
+
+use super::dispatch::call_tool;
+use super::protocol::{INTERNAL_ERROR, INVALID_PARAMS};
+
+/// 预填的日志行数
+const LOG_LINES: u64 = 50;
+
+/// tests/cucumber.rs 中可用的步骤
+const STEPS: &str = "\
+假设 游戏已启动
+当 点击按钮 \"<testId>\"
+那么 日志中应该包含 \"<文本>\"
+那么 存在 <N> 个类型为 \"<组件>\" 的组件
+那么 画面应与基准图 \"<name>\" 一致
+那么 忽略 \"<testId>\" 后画面应与基准图 \"<name>\" 相似
+那么 元素 \"<testId>\" 的颜色应接近 \"<颜色>\"";
+
+struct Prompt {
+    name: &'static str,
+    description: &'static str,
+    /// (参数名, 说明, 是否必填)
+    arguments: &'static [(&'static str, &'static str, bool)],
+}
+
+const PROMPTS: &[Prompt] = &[
+    Prompt {
+        name: "write_scenario",
+        description: "探索当前 UI 并编写 Gherkin 场景",
+        arguments: &[("feature", "要覆盖的功能或行为（可选）", false)],
+    },
+    Prompt {
+        name: "reproduce_bug",
+        description: "根据日志与 bug 描述复现问题，并写成失败的场景",
+        arguments: &[("bug", "bug 的现象描述", true)],
+    },
+    Prompt {
+        name: "verify_visual_change",
+        description: "验证一次视觉改动：截图、与基准图对比、检查关键元素颜色",
+        arguments: &[
+            ("change", "预期的视觉变化", true),
+            ("baseline", "基准图名称（可选）", false),
+        ],
+    },
+];
+
+/// prompts/list
+pub fn list() -> Value {
+    let prompts: Vec<Value> = PROMPTS
+        .iter()
+        .map(|p| {
+            let arguments: Vec<Value> = p
+                .arguments
+                .iter()
+                .map(|(name, description, required)| {
+                    json!({ "name": name, "description": description, "required": required })
+                })
+                .collect();
+            json!({ "name": p.name, "description": p.description, "arguments": arguments })
+        })
+        .collect();
+    json!({ "prompts": prompts })
+}
+
+/// prompts/get；失败时返回 JSON-RPC 错误码与原因
+pub async fn get(
+    sender: &Sender<TestMessage>,
+    name: &str,
+    arguments: &Map<String, Value>,
+) -> Result<Value, (i32, String)> {
+    let prompt = PROMPTS
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| (INVALID_PARAMS, format!("未知提示模板: {}", name)))?;
+    let arg = |key: &str| arguments.get(key).and_then(Value::as_str).unwrap_or("");
+    if let Some((missing, _, _)) = prompt
+        .arguments
+        .iter()
+        .find(|(key, _, required)| *required && arg(key).is_empty())
+    {
+        return Err((INVALID_PARAMS, format!("缺少参数: {}", missing)));
+    }
+
+    let task = match name {
+        "write_scenario" => {
+            let focus = match arg("feature") {
+                "" => "从快照中挑选尚未被 tests/features/ 覆盖的交互".to_string(),
+                feature => format!("要覆盖的功能：{}", feature),
+            };
+            format!(
+                "探索游戏当前的 UI，为它编写一个 Gherkin 场景（`# language: zh-CN`，放在 tests/features/ 下）。\n\
+                 {}\n\n\
+                 先用 take_snapshot / hover_by_id / click_by_id / screenshot 确认元素与行为，再写场景。\
+                 优先使用下列已有步骤，确实需要时再在 tests/cucumber.rs 中新增步骤：\n{}",
+                focus, STEPS
+            )
+        }
+        "reproduce_bug" => format!(
+            "复现这个 bug：{}\n\n\
+             从下面的日志中找出相关记录，用工具在游戏中重现操作序列（需要时用 highlight 和 screenshot 记录证据），\
+             然后写一个在修复前会失败的 Gherkin 场景。可用步骤：\n{}",
+            arg("bug"),
+            STEPS
+        ),
+        _ => {
+            let baseline = match arg("baseline") {
+                "" => "与相关的基准图对比".to_string(),
+                baseline => format!("与基准图 \"{}\" 对比", baseline),
+            };
+            format!(
+                "验证这次视觉改动：{}\n\n\
+                 用 screenshot（stable: true）截图，{}（compare_screenshot，有随机内容时加 mask），\
+                 再用 probe_pixels 检查关键元素的颜色。改动符合预期时以 --update-baselines 更新基准图，并说明更新理由。",
+                arg("change"),
+                baseline
+            )
+        }
+    };
+
+    let snapshot = call_tool(sender, "take_snapshot", &json!({}))
+        .await
+        .map_err(|e| (INTERNAL_ERROR, e.to_string()))?;
+    let logs = call_tool(sender, "console_messages", &json!({ "lines": LOG_LINES }))
+        .await
+        .map_err(|e| (INTERNAL_ERROR, e.to_string()))?;
+    let text = format!(
+        "{}\n\n## 当前 UI 快照（take_snapshot）\n\n```json\n{}\n```\n\n## 最近 {} 行日志（console_messages）\n\n{}",
+        task,
+        serde_json::to_string_pretty(&snapshot.data).unwrap_or_default(),
+        LOG_LINES,
+        format_logs(&logs.data["entries"])
+    );
+    Ok(json!({
+        "description": prompt.description,
+        "messages": [{ "role": "user", "content": { "type": "text", "text": text } }]
+    }))
+}
+
+/// 日志条目格式化为纯文本，每行一条
+fn format_logs(entries: &Value) -> String {
+    let lines: Vec<String> = entries
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .map(|e| {
+                    format!(
+                        "{} [{}] {}",
+                        e["timestamp"].as_str().unwrap_or_default(),
+                        e["level"].as_str().unwrap_or_default(),
+                        e["message"].as_str().unwrap_or_default()
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if lines.is_empty() {
+        "（暂无日志）".to_string()
+    } else {
+        format!("```\n{}\n```", lines.join("\n"))
+    }
+}
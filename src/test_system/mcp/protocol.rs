@@ -1,20 +1,98 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// JSON-RPC 2.0 错误码
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+/// MCP：资源不存在
+pub const RESOURCE_NOT_FOUND: i32 = -32002;
+
+/// 支持的 MCP 协议版本，新的在前
+pub const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// 协商协议版本：支持客户端请求的版本时使用该版本，否则返回最新版本，由客户端决定是否继续
+pub fn negotiate_version(requested: Option<&str>) -> &'static str {
+    PROTOCOL_VERSIONS
+        .iter()
+        .find(|&&version| Some(version) == requested)
+        .unwrap_or(&PROTOCOL_VERSIONS[0])
+}
 
-#[derive(Deserialize)]
 pub struct RpcReq {
-    #[allow(dead_code)]
-    pub jsonrpc: String,
+    /// 缺少 id 的是通知，不返回响应
     pub id: Option<Value>,
     pub method: String,
     pub params: Option<Value>,
 }
 
+impl RpcReq {
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// 请求体：单条消息或批量消息；无效的消息已转换为对应的错误响应
+pub enum RpcBody {
+    Single(Result<RpcReq, RpcResp>),
+    Batch(Vec<Result<RpcReq, RpcResp>>),
+}
+
+impl RpcBody {
+    /// 解析请求体；不是合法 JSON 或是空数组时返回单个错误响应
+    pub fn parse(bytes: &[u8]) -> Result<Self, RpcResp> {
+        let value: Value = serde_json::from_slice(bytes)
+            .map_err(|e| RpcResp::err(Value::Null, PARSE_ERROR, format!("解析错误: {}", e)))?;
+        match value {
+            Value::Array(items) if items.is_empty() => Err(RpcResp::err(
+                Value::Null,
+                INVALID_REQUEST,
+                "批量请求不能为空",
+            )),
+            Value::Array(items) => Ok(Self::Batch(items.into_iter().map(parse_message).collect())),
+            value => Ok(Self::Single(parse_message(value))),
+        }
+    }
+}
+
+/// 校验单条消息是否为合法的 JSON-RPC 2.0 请求或通知
+fn parse_message(value: Value) -> Result<RpcReq, RpcResp> {
+    let Value::Object(mut object) = value else {
+        return Err(invalid(Value::Null, "请求必须是对象"));
+    };
+    // id 只能是字符串、数字或 null；缺少 id 的是通知
+    let id = match object.remove("id") {
+        None => None,
+        Some(id @ (Value::String(_) | Value::Number(_) | Value::Null)) => Some(id),
+        Some(_) => return Err(invalid(Value::Null, "id 必须是字符串、数字或 null")),
+    };
+    let reply_id = id.clone().unwrap_or(Value::Null);
+    if object.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(invalid(reply_id, "jsonrpc 必须是 \"2.0\""));
+    }
+    let method = match object.remove("method") {
+        Some(Value::String(method)) => method,
+        _ => return Err(invalid(reply_id, "缺少 method 或 method 不是字符串")),
+    };
+    let params = match object.remove("params") {
+        None => None,
+        Some(params @ (Value::Object(_) | Value::Array(_))) => Some(params),
+        Some(_) => return Err(invalid(reply_id, "params 必须是对象或数组")),
+    };
+    Ok(RpcReq { id, method, params })
+}
+
+fn invalid(id: Value, message: &str) -> RpcResp {
+    RpcResp::err(id, INVALID_REQUEST, format!("无效请求: {}", message))
+}
+
 #[derive(Serialize)]
 pub struct RpcResp {
     pub jsonrpc: &'static str,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<Value>,
+    /// 无法确定请求 id 时（解析错误等）为 null
+    pub id: Value,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -28,7 +106,7 @@ pub struct RpcErr {
 }
 
 impl RpcResp {
-    pub fn ok(id: Option<Value>, result: Value) -> Self {
+    pub fn ok(id: Value, result: Value) -> Self {
         Self {
             jsonrpc: "2.0",
             id,
@@ -37,7 +115,7 @@ impl RpcResp {
         }
     }
 
-    pub fn err(id: Option<Value>, code: i32, msg: impl Into<String>) -> Self {
+    pub fn err(id: Value, code: i32, msg: impl Into<String>) -> Self {
         Self {
             jsonrpc: "2.0",
             id,
@@ -49,3 +127,57 @@ impl RpcResp {
         }
     }
 }
+
+/// 取对象形式的 params
+pub fn params_object(req: &RpcReq) -> Option<&Map<String, Value>> {
+    req.params.as_ref().and_then(Value::as_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(result: Result<RpcReq, RpcResp>) -> i32 {
+        result.err().and_then(|resp| resp.error).unwrap().code
+    }
+
+    #[test]
+    fn test_parse_body() {
+        let err = RpcBody::parse(b"{not json").err().unwrap();
+        assert_eq!(err.error.unwrap().code, PARSE_ERROR);
+        assert_eq!(err.id, Value::Null);
+        let err = RpcBody::parse(b"[]").err().unwrap();
+        assert_eq!(err.error.unwrap().code, INVALID_REQUEST);
+
+        let body = br#"[
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "1.0", "id": "a", "method": "ping"},
+            {"jsonrpc": "2.0", "id": 2, "method": "ping", "params": 3},
+            1
+        ]"#;
+        let Ok(RpcBody::Batch(mut items)) = RpcBody::parse(body) else {
+            panic!("应解析为批量请求");
+        };
+        assert_eq!(items.len(), 5);
+        let non_object = items.pop().unwrap();
+        assert_eq!(error_code(non_object), INVALID_REQUEST);
+        let bad_params = items.pop().unwrap();
+        assert_eq!(bad_params.as_ref().err().unwrap().id, 2);
+        assert_eq!(error_code(bad_params), INVALID_REQUEST);
+        let bad_version = items.pop().unwrap();
+        assert_eq!(bad_version.as_ref().err().unwrap().id, "a");
+        assert!(items.pop().unwrap().ok().unwrap().is_notification());
+        let request = items.pop().unwrap().ok().unwrap();
+        assert_eq!(request.id, Some(Value::from(1)));
+        assert_eq!(request.method, "tools/list");
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(negotiate_version(Some("2024-11-05")), "2024-11-05");
+        assert_eq!(negotiate_version(Some("2025-03-26")), "2025-03-26");
+        assert_eq!(negotiate_version(Some("1999-01-01")), PROTOCOL_VERSIONS[0]);
+        assert_eq!(negotiate_version(None), PROTOCOL_VERSIONS[0]);
+    }
+}
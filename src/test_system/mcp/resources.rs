@@ -0,0 +1,235 @@
+//! MCP 资源：游戏日志、UI 快照与测试日志目录下的截图
+//!
+//! - `game://logs/game.log`        当前日志文件（TEST_LOG_FILE）
+//! - `game://snapshot/ui`          take_snapshot 的 UI 节点树
+//! - `game://screenshots/{name}`   日志目录下的图片，name 为相对路径
+//!
+//! 订阅的资源由 watch 每隔 WATCH_INTERVAL 轮询一次，变化时发送 notifications/resources/updated；
+//! 截图列表变化时发送 notifications/resources/list_changed
+
+use base64::Engine;
+use crossbeam_channel::Sender;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::test_system::channel::TestMessage;
+
+use super::dispatch::{artifact_dir, call_tool};
+use super::stream;
+
+const LOG_URI: &str = "game://logs/game.log";
+const SNAPSHOT_URI: &str = "game://snapshot/ui";
+const SCREENSHOT_PREFIX: &str = "game://screenshots/";
+/// 截图目录的最大遍历深度（visual/ 等子目录）
+const SCREENSHOT_DEPTH: usize = 3;
+/// 订阅资源的轮询间隔
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 已订阅的资源 URI
+static SUBSCRIPTIONS: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// resources/list
+pub fn list() -> Value {
+    let mut resources = vec![
+        json!({
+            "uri": LOG_URI,
+            "name": "game.log",
+            "description": "游戏日志（当前测试场景的 TEST_LOG_FILE）",
+            "mimeType": "text/plain"
+        }),
+        json!({
+            "uri": SNAPSHOT_URI,
+            "name": "UI 快照",
+            "description": "当前 UI 节点树，与 take_snapshot 工具的结果相同",
+            "mimeType": "application/json"
+        }),
+    ];
+    for name in screenshot_names() {
+        resources.push(json!({
+            "uri": format!("{}{}", SCREENSHOT_PREFIX, name),
+            "name": name,
+            "mimeType": image_mime(Path::new(&name))
+        }));
+    }
+    json!({ "resources": resources })
+}
+
+/// resources/templates/list
+pub fn templates() -> Value {
+    json!({
+        "resourceTemplates": [{
+            "uriTemplate": format!("{}{{name}}", SCREENSHOT_PREFIX),
+            "name": "截图",
+            "description": "测试日志目录下的图片（screenshot / compare_screenshot / 录制产物），name 为相对路径，如 visual/main-screen.diff.png"
+        }]
+    })
+}
+
+/// resources/read；资源不存在时返回 Err(None)，读取失败返回 Err(Some(原因))
+pub async fn read(sender: &Sender<TestMessage>, uri: &str) -> Result<Value, Option<String>> {
+    let contents = match uri {
+        LOG_URI => {
+            let bytes = std::fs::read(log_file()).map_err(|e| Some(e.to_string()))?;
+            json!({
+                "uri": uri,
+                "mimeType": "text/plain",
+                "text": String::from_utf8_lossy(&bytes)
+            })
+        }
+        SNAPSHOT_URI => {
+            let snapshot = call_tool(sender, "take_snapshot", &json!({}))
+                .await
+                .map_err(|e| Some(e.to_string()))?;
+            json!({
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&snapshot.data).unwrap_or_default()
+            })
+        }
+        _ => {
+            let path = uri
+                .strip_prefix(SCREENSHOT_PREFIX)
+                .and_then(screenshot_path)
+                .ok_or(None)?;
+            let bytes = std::fs::read(&path).map_err(|_| None)?;
+            json!({
+                "uri": uri,
+                "mimeType": image_mime(&path),
+                "blob": base64::engine::general_purpose::STANDARD.encode(bytes)
+            })
+        }
+    };
+    Ok(json!({ "contents": [contents] }))
+}
+
+/// URI 是否指向已知资源（订阅前校验）
+pub fn exists(uri: &str) -> bool {
+    matches!(uri, LOG_URI | SNAPSHOT_URI)
+        || uri
+            .strip_prefix(SCREENSHOT_PREFIX)
+            .and_then(screenshot_path)
+            .is_some()
+}
+
+pub fn subscribe(uri: &str) {
+    SUBSCRIPTIONS.lock().unwrap().insert(uri.to_string());
+}
+
+pub fn unsubscribe(uri: &str) {
+    SUBSCRIPTIONS.lock().unwrap().remove(uri);
+}
+
+/// 轮询已订阅的资源与截图列表，变化时通知客户端
+pub async fn watch(sender: Sender<TestMessage>) {
+    let mut versions: HashMap<String, u64> = HashMap::new();
+    let mut names = screenshot_names();
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let current = screenshot_names();
+        if current != names {
+            names = current;
+            stream::notify("notifications/resources/list_changed", json!({}));
+        }
+
+        let subscribed: Vec<String> = SUBSCRIPTIONS.lock().unwrap().iter().cloned().collect();
+        versions.retain(|uri, _| subscribed.contains(uri));
+        for uri in subscribed {
+            let Some(version) = version(&sender, &uri).await else {
+                continue;
+            };
+            // 订阅后的第一次轮询只记录版本
+            if versions
+                .insert(uri.clone(), version)
+                .is_some_and(|v| v != version)
+            {
+                stream::notify("notifications/resources/updated", json!({ "uri": uri }));
+            }
+        }
+    }
+}
+
+/// 资源当前内容的版本：文件按长度与修改时间，UI 快照按内容哈希
+async fn version(sender: &Sender<TestMessage>, uri: &str) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    let path = match uri {
+        LOG_URI => log_file(),
+        SNAPSHOT_URI => {
+            let snapshot = call_tool(sender, "take_snapshot", &json!({})).await.ok()?;
+            snapshot.data.to_string().hash(&mut hasher);
+            return Some(hasher.finish());
+        }
+        _ => screenshot_path(uri.strip_prefix(SCREENSHOT_PREFIX)?)?,
+    };
+    let metadata = std::fs::metadata(path).ok()?;
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok()?.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+fn log_file() -> PathBuf {
+    std::env::var("TEST_LOG_FILE")
+        .unwrap_or_else(|_| "logs/game.log".to_string())
+        .into()
+}
+
+/// 日志目录下的图片，按相对路径排序
+fn screenshot_names() -> Vec<String> {
+    let root = artifact_dir("");
+    let mut names = Vec::new();
+    collect_images(&root, &root, 0, &mut names);
+    names.sort();
+    names
+}
+
+fn collect_images(root: &Path, dir: &Path, depth: usize, names: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth + 1 < SCREENSHOT_DEPTH {
+                collect_images(root, &path, depth + 1, names);
+            }
+        } else if is_image(&path) {
+            if let Ok(relative) = path.strip_prefix(root) {
+                let parts: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+                names.push(parts.join("/"));
+            }
+        }
+    }
+}
+
+/// 截图名对应的文件；拒绝绝对路径与 `..`，只允许图片
+fn screenshot_path(name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    let safe = !name.is_empty()
+        && relative
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+    let path = artifact_dir("").join(relative);
+    (safe && is_image(&path) && path.is_file()).then_some(path)
+}
+
+fn is_image(path: &Path) -> bool {
+    image_mime(path) != "application/octet-stream"
+}
+
+fn image_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
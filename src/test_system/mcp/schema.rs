@@ -0,0 +1,311 @@
+//! 工具参数的 JSON Schema 校验
+//!
+//! 只实现 schemars 生成与 settle 参数用到的子集：type、enum、const、properties、required、
+//! additionalProperties、items、minItems / maxItems、minimum / maximum（含 exclusive）、
+//! anyOf / oneOf / allOf 与指向 `#/$defs/…` 的 $ref
+
+use serde_json::Value;
+use std::fmt;
+
+/// 校验失败：出错的参数路径（如 `stable.frames`、`ids[1]`，顶层为空）与错误信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// 按 schema 校验 value，失败时返回带参数路径的错误
+pub fn validate(schema: &Value, value: &Value) -> Result<(), ValidationError> {
+    Validator { root: schema }.check(schema, value, "")
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl Validator<'_> {
+    fn check(&self, schema: &Value, value: &Value, path: &str) -> Result<(), ValidationError> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => return Err(error(path, "不允许该参数")),
+            Value::Object(schema) => schema,
+            _ => return Ok(()),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = reference
+                .strip_prefix("#/$defs/")
+                .and_then(|name| self.root.get("$defs")?.get(name))
+                .ok_or_else(|| error(path, &format!("的 schema 引用不存在: {}", reference)))?;
+            self.check(target, value, path)?;
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| is_type(value, t)) {
+                return Err(error(
+                    path,
+                    &format!("应为 {}，实际为 {}", types.join(" / "), type_name(value)),
+                ));
+            }
+        }
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                let options: Vec<String> = options.iter().map(Value::to_string).collect();
+                return Err(error(path, &format!("应为 {} 之一", options.join(" / "))));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                return Err(error(path, &format!("应为 {}", expected)));
+            }
+        }
+
+        if let Some(n) = value.as_f64() {
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                return Err(error(path, &format!("不能小于 {}", min)));
+            }
+            if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                return Err(error(path, &format!("不能大于 {}", max)));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                return Err(error(path, &format!("必须大于 {}", min)));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                return Err(error(path, &format!("必须小于 {}", max)));
+            }
+        }
+
+        if let Value::Object(object) = value {
+            for name in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(name) {
+                    let path = join(path, name);
+                    return Err(ValidationError {
+                        message: format!("缺少参数: {}", path),
+                        path,
+                    });
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, item) in object {
+                let item_path = join(path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(property) => self.check(property, item, &item_path)?,
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            self.check(additional, item, &item_path)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Value::Array(items) = value {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if len < min {
+                    return Err(error(path, &format!("至少需要 {} 项", min)));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if len > max {
+                    return Err(error(path, &format!("最多 {} 项", max)));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    self.check(item_schema, item, &format!("{}[{}]", path, i))?;
+                }
+            }
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for sub in all {
+                self.check(sub, value, path)?;
+            }
+        }
+        if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+            self.check_alternatives(any, value, path, false)?;
+        }
+        if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+            self.check_alternatives(one, value, path, true)?;
+        }
+        Ok(())
+    }
+
+    /// anyOf / oneOf：只有一个分支的类型与参数相符时返回该分支的具体错误，便于定位
+    fn check_alternatives(
+        &self,
+        alternatives: &[Value],
+        value: &Value,
+        path: &str,
+        exactly_one: bool,
+    ) -> Result<(), ValidationError> {
+        let results: Vec<Result<(), ValidationError>> = alternatives
+            .iter()
+            .map(|sub| self.check(sub, value, path))
+            .collect();
+        let matched = results.iter().filter(|r| r.is_ok()).count();
+        if exactly_one && matched > 1 {
+            return Err(error(path, "同时匹配了多种形式"));
+        }
+        if matched > 0 {
+            return Ok(());
+        }
+        let candidates: Vec<&Result<(), ValidationError>> = alternatives
+            .iter()
+            .zip(&results)
+            .filter(|(sub, _)| self.type_matches(sub, value))
+            .map(|(_, result)| result)
+            .collect();
+        match candidates[..] {
+            [Err(only)] => Err(only.clone()),
+            _ => Err(error(path, "不符合任何一种允许的形式")),
+        }
+    }
+
+    /// 只比较顶层 type（解析 $ref）
+    fn type_matches(&self, schema: &Value, value: &Value) -> bool {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => match reference
+                .strip_prefix("#/$defs/")
+                .and_then(|name| self.root.get("$defs")?.get(name))
+            {
+                Some(target) => target,
+                None => return false,
+            },
+            None => schema,
+        };
+        match schema.get("type") {
+            Some(Value::String(t)) => is_type(value, t),
+            Some(Value::Array(ts)) => ts
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|t| is_type(value, t)),
+            _ => true,
+        }
+    }
+}
+
+fn is_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn error(path: &str, reason: &str) -> ValidationError {
+    let message = if path.is_empty() {
+        format!("参数{}", reason)
+    } else {
+        format!("参数 {} {}", path, reason)
+    };
+    ValidationError {
+        path: path.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "x": { "type": "number" },
+                "format": { "type": "string", "enum": ["png", "jpeg"] },
+                "quality": { "type": "integer", "minimum": 1, "maximum": 100 },
+                "stable": {
+                    "anyOf": [
+                        { "type": "boolean" },
+                        { "type": "integer", "minimum": 2 },
+                        { "$ref": "#/$defs/Stable" }
+                    ]
+                },
+                "ids": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+            },
+            "required": ["x"],
+            "$defs": {
+                "Stable": {
+                    "type": "object",
+                    "properties": { "frames": { "type": "integer", "minimum": 2 } }
+                }
+            }
+        });
+        assert!(validate(&schema, &json!({ "x": 1.5, "stable": true })).is_ok());
+        assert!(validate(&schema, &json!({ "x": 1, "stable": { "frames": 4 } })).is_ok());
+        let check = |value: Value| validate(&schema, &value).unwrap_err().to_string();
+        assert_eq!(check(json!({})), "缺少参数: x");
+        assert_eq!(
+            check(json!({ "x": "1" })),
+            "参数 x 应为 number，实际为 string"
+        );
+        assert!(check(json!({ "x": 1, "format": "gif" })).contains("\"png\" / \"jpeg\""));
+        assert_eq!(
+            check(json!({ "x": 1, "quality": 0 })),
+            "参数 quality 不能小于 1"
+        );
+        assert_eq!(
+            check(json!({ "x": 1, "quality": 2.5 })),
+            "参数 quality 应为 integer，实际为 number"
+        );
+        // 只有对象分支类型匹配，报告该分支内部的错误
+        assert_eq!(
+            check(json!({ "x": 1, "stable": { "frames": 1 } })),
+            "参数 stable.frames 不能小于 2"
+        );
+        let error = validate(&schema, &json!({ "x": 1, "stable": { "frames": 1 } })).unwrap_err();
+        assert_eq!(error.path, "stable.frames");
+        assert_eq!(
+            check(json!({ "x": 1, "ids": ["a", 2] })),
+            "参数 ids[1] 应为 string，实际为 integer"
+        );
+    }
+}
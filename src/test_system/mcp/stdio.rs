@@ -0,0 +1,112 @@
+//! stdio 传输：stdin 每行一条 JSON-RPC 消息（或批量数组），响应与服务端通知按行写到 stdout
+//!
+//! 与 HTTP 端点共用 handler 的消息处理。每行在独立任务中处理，调用进行中也能收到
+//! notifications/cancelled；服务端通知（日志、进度、列表变化）全部经同一个输出流发出。
+//! stdout 只承载协议消息，游戏日志由 log_setup 写入日志文件。
+//! stdin 关闭后经 TestMessage::Exit 让游戏按 AppExit 正常退出
+
+use crossbeam_channel::Sender;
+use log::{error, info, warn};
+use serde_json::Value;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::test_system::channel::TestMessage;
+
+use super::context;
+use super::handler::handle_message;
+use super::protocol::RpcBody;
+use super::stream;
+
+/// 等待游戏确认退出请求的时间
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 处理 stdin 上的消息，直到 stdin 关闭（客户端断开）后请求游戏退出并返回
+pub async fn serve(sender: Sender<TestMessage>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    // stdio 只有一个输出流，与请求无关的服务端通知也投递到这里
+    let _guard = stream::open(tx.clone());
+    tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let line = format!("{}\n", message);
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) if line.trim().is_empty() => {}
+            Ok(Some(line)) => {
+                tokio::spawn(handle_line(sender.clone(), line, tx.clone()));
+            }
+            Ok(None) => break,
+            Err(e) => {
+                error!("读取 stdin 失败: {}", e);
+                break;
+            }
+        }
+    }
+    info!("stdin 已关闭，游戏退出");
+    shutdown(&sender).await;
+}
+
+/// 请求游戏正常退出
+async fn shutdown(sender: &Sender<TestMessage>) {
+    let (tx, rx) = oneshot::channel();
+    if sender.send(TestMessage::Exit { response: tx }).is_err() {
+        return;
+    }
+    if let Err(e) = context::wait(rx, EXIT_TIMEOUT, "游戏退出").await {
+        warn!("退出请求未得到确认: {}", e);
+    }
+}
+
+/// 处理一行输入；只含通知时不输出
+async fn handle_line(sender: Sender<TestMessage>, line: String, tx: mpsc::UnboundedSender<Value>) {
+    let output = match RpcBody::parse(line.as_bytes()) {
+        Ok(RpcBody::Single(message)) => handle_message(&sender, message, Some(&tx))
+            .await
+            .map(|resp| serde_json::to_value(resp).unwrap_or_default()),
+        Ok(RpcBody::Batch(messages)) => {
+            let mut responses = Vec::new();
+            for message in messages {
+                responses.extend(handle_message(&sender, message, Some(&tx)).await);
+            }
+            (!responses.is_empty()).then(|| serde_json::to_value(responses).unwrap_or_default())
+        }
+        Err(resp) => Some(serde_json::to_value(resp).unwrap_or_default()),
+    };
+    if let Some(output) = output {
+        let _ = tx.send(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handle_line() {
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let line = r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#;
+        handle_line(sender.clone(), line.to_string(), tx.clone()).await;
+        assert!(rx.try_recv().is_err());
+
+        let line = r#"[{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}, {not json"#;
+        handle_line(sender.clone(), line.to_string(), tx.clone()).await;
+        assert_eq!(rx.try_recv().unwrap()["error"]["code"], -32700);
+
+        let line = r#"[{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}]"#;
+        handle_line(sender, line.to_string(), tx).await;
+        let responses = rx.try_recv().unwrap();
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"]["tools"].is_array());
+    }
+}
@@ -0,0 +1,131 @@
+//! Streamable HTTP 的服务端推送：GET /mcp 打开的 SSE 流与 POST 请求的 SSE 响应
+//!
+//! 与请求无关的服务端通知（游戏日志、列表变化、资源更新）只投递到 GET 打开的独立流，
+//! 每条只发给最早打开且仍在连接的一个流，没有可用的流时丢弃。
+//! 与请求相关的消息（如进度）由调用上下文直接发到该请求自己的 SSE 响应，不经过这里
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::mpsc;
+
+static STREAMS: LazyLock<Streams> = LazyLock::new(Streams::default);
+
+/// 转发游戏日志的最低级别（MCP 日志级别序号），默认 warning
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(3);
+
+/// MCP 日志级别，按严重程度递增（RFC 5424）
+const LOG_LEVELS: [&str; 8] = [
+    "debug",
+    "info",
+    "notice",
+    "warning",
+    "error",
+    "critical",
+    "alert",
+    "emergency",
+];
+
+/// 已打开的独立流（GET /mcp）
+#[derive(Default)]
+struct Streams {
+    next_id: AtomicU64,
+    open: Mutex<Vec<(u64, mpsc::UnboundedSender<Value>)>>,
+}
+
+/// 已登记的流，drop 时注销
+pub struct StreamGuard(u64);
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        STREAMS.open.lock().unwrap().retain(|(id, _)| *id != self.0);
+    }
+}
+
+/// 登记一个接收服务端通知的独立流
+pub fn open(tx: mpsc::UnboundedSender<Value>) -> StreamGuard {
+    let id = STREAMS.next_id.fetch_add(1, Ordering::Relaxed);
+    STREAMS.open.lock().unwrap().push((id, tx));
+    StreamGuard(id)
+}
+
+/// 构造 JSON-RPC 通知
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// 发送与请求无关的服务端通知
+pub fn notify(method: &str, params: Value) {
+    let mut open = STREAMS.open.lock().unwrap();
+    open.retain(|(_, tx)| !tx.is_closed());
+    if let Some((_, tx)) = open.first() {
+        let _ = tx.send(notification(method, params));
+    }
+}
+
+/// 把消息通道包装为 SSE 响应；guard 随流一起释放，客户端断开后自动注销
+pub fn sse(
+    rx: mpsc::UnboundedReceiver<Value>,
+    guard: Option<StreamGuard>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::unfold((rx, guard), |(mut rx, guard)| async move {
+        let message = rx.recv().await?;
+        let event = Event::default().event("message").data(message.to_string());
+        Some((Ok(event), (rx, guard)))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// logging/setLevel：设置转发游戏日志的最低级别
+pub fn set_log_level(level: &str) -> Result<(), String> {
+    let index = LOG_LEVELS
+        .iter()
+        .position(|l| *l == level)
+        .ok_or_else(|| format!("无效的日志级别: {}", level))?;
+    LOG_LEVEL.store(index as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 把游戏日志作为 notifications/message 推送给客户端
+pub fn forward_log(level: log::Level, target: &str, message: String) {
+    let level = match level {
+        log::Level::Error => "error",
+        log::Level::Warn => "warning",
+        log::Level::Info => "info",
+        log::Level::Debug | log::Level::Trace => "debug",
+    };
+    let index = LOG_LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+    if (index as u8) < LOG_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    notify(
+        "notifications/message",
+        json!({ "level": level, "logger": target, "data": message }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_goes_to_one_stream() {
+        let (first_tx, mut first_rx) = mpsc::unbounded_channel();
+        let (second_tx, mut second_rx) = mpsc::unbounded_channel();
+        let first = open(first_tx);
+        let _second = open(second_tx);
+
+        // 只投递到最早打开的流
+        notify("notifications/test", json!({ "n": 1 }));
+        assert_eq!(first_rx.try_recv().unwrap()["params"]["n"], 1);
+        assert!(second_rx.try_recv().is_err());
+
+        // 该流断开后改投下一个
+        drop(first);
+        notify("notifications/test", json!({ "n": 2 }));
+        assert_eq!(second_rx.try_recv().unwrap()["params"]["n"], 2);
+    }
+}
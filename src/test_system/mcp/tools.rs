@@ -1,148 +1,404 @@
+//! MCP 工具注册表
+//!
+//! 每个工具实现 [`Tool`]：声明 serde 参数结构体，inputSchema 由该结构体生成（字段的文档注释即参数说明），
+//! 调用前先按 schema 校验参数再反序列化。结果类型是对象结构体时同样生成 outputSchema，
+//! 结果以 structuredContent 返回，客户端不必从文本中解析。tools/list 与 call_tool 都从注册表派生，工具名只出现一次。
+//! 游戏自定义的工具（register_test_tool）直接给出 inputSchema，同样在调用前校验。
+//! 工具失败时返回 [`ToolError`]，以 `isError: true` 的结果交给客户端，code 可直接用于分支判断
+
+use crossbeam_channel::Sender;
+use futures_util::future::BoxFuture;
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
 
-pub fn tool_list() -> Value {
-    json!({
-        "tools": [
-            {
-                "name": "health",
-                "description": "检查游戏测试服务器是否运行",
-                "inputSchema": { "type": "object", "properties": {} }
-            },
-            {
-                "name": "take_snapshot",
-                "description": "获取游戏 UI 节点树快照（类 CDP take_snapshot）。返回所有节点：uid、name、nodeType、text、testId、visible、x/y/width/height、parentUid。",
-                "inputSchema": { "type": "object", "properties": {} }
-            },
-            {
-                "name": "component_counts",
-                "description": "查询游戏中各组件的实体数量（Ball、Button 等）",
-                "inputSchema": { "type": "object", "properties": {} }
-            },
-            {
-                "name": "screenshot",
-                "description": "截取游戏画面并保存到指定路径",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "path": { "type": "string", "description": "保存路径，如 screenshots/test.png" }
-                    },
-                    "required": ["path"]
-                }
-            },
-            {
-                "name": "click",
-                "description": "在屏幕坐标 (x, y) 处点击",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "x": { "type": "number", "description": "屏幕 X 坐标（像素）" },
-                        "y": { "type": "number", "description": "屏幕 Y 坐标（像素）" }
-                    },
-                    "required": ["x", "y"]
-                }
-            },
-            {
-                "name": "hover",
-                "description": "将鼠标悬停在屏幕坐标 (x, y) 处",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "x": { "type": "number" },
-                        "y": { "type": "number" }
-                    },
-                    "required": ["x", "y"]
-                }
-            },
-            {
-                "name": "click_by_id",
-                "description": "按 test_id / Name / uid(bits:xxxx) 点击 UI 元素（类 CDP click(uid)）",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": { "type": "string", "description": "元素标识：testId / Name / bits:xxxx" }
-                    },
-                    "required": ["id"]
-                }
-            },
-            {
-                "name": "hover_by_id",
-                "description": "按 test_id / Name / uid(bits:xxxx) 悬停 UI 元素",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": { "type": "string" }
-                    },
-                    "required": ["id"]
-                }
-            },
-            {
-                "name": "click_button",
-                "description": "按按钮的 Name 或 test_id 点击按钮",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "button_name": { "type": "string", "description": "按钮名称，如 main-button" }
-                    },
-                    "required": ["button_name"]
-                }
-            },
-            {
-                "name": "press_key",
-                "description": "模拟键盘按键。支持：Space、Enter、Escape、Tab、Backspace、Delete、ArrowUp/Down/Left/Right、F1-F12、KeyA-Z、Digit0-9",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "key": { "type": "string", "description": "按键名，如 Space、Enter、ArrowUp" }
-                    },
-                    "required": ["key"]
-                }
-            },
-            {
-                "name": "fill",
-                "description": "向指定 UI 元素（Text 组件）填充文本（先清空原内容）",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "id": { "type": "string", "description": "元素标识" },
-                        "value": { "type": "string", "description": "要填充的文本" }
-                    },
-                    "required": ["id", "value"]
-                }
-            },
-            {
-                "name": "drag",
-                "description": "从源元素拖拽到目标元素",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "from_id": { "type": "string" },
-                        "to_id": { "type": "string" }
-                    },
-                    "required": ["from_id", "to_id"]
-                }
-            },
-            {
-                "name": "console_messages",
-                "description": "读取后端游戏日志文件，返回最近 N 行（类 CDP list_console_messages）",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "lines": { "type": "integer", "description": "返回行数，默认 50", "default": 50 },
-                        "log_file": { "type": "string", "description": "日志文件路径（可选）" }
-                    }
+use crate::test_system::channel::{ActionError, ScreenshotError, TestMessage, TestStage};
+
+use super::schema;
+
+/// 一个 MCP 工具
+pub trait Tool: 'static {
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// 命令在 Bevy 帧内的执行阶段（None 表示不经过游戏主循环）
+    const STAGE: Option<TestStage> = None;
+    /// 参数结构体；不认识的字段（如 settle）会被忽略
+    type Args: DeserializeOwned + JsonSchema + Send;
+    /// 结果类型；生成的 schema 是对象时声明为 outputSchema。返回图像或结构不固定的工具使用 Value
+    type Output: Serialize + JsonSchema + Send;
+
+    fn call(
+        sender: &Sender<TestMessage>,
+        args: Self::Args,
+    ) -> impl Future<Output = Result<Self::Output, ToolError>> + Send;
+}
+
+/// 工具调用失败：code 是稳定的机器可读错误码，message 是给人看的说明，details 为可选的附加信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolError {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl ToolError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// 参数不符合 inputSchema 或取值无效
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new("invalid_argument", message)
+    }
+
+    /// 选择器、节点或文件不存在
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new("not_found", message)
+    }
+
+    /// 等待游戏响应超时
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::new("timeout", message)
+    }
+
+    /// 与游戏主循环的通道已关闭（游戏退出或命令被丢弃）
+    pub fn channel_closed(message: impl Into<String>) -> Self {
+        Self::new("channel_closed", message)
+    }
+
+    /// 工具内部错误
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new("internal", message)
+    }
+
+    /// 未注册的工具；作为 JSON-RPC 协议错误返回，而不是 isError 结果
+    pub fn unknown_tool(name: &str) -> Self {
+        Self::new("unknown_tool", format!("未知工具: {}", name))
+    }
+
+    /// 错误的 JSON 表示 `{code, message, details}`
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({ "code": self.code, "message": self.message });
+        if let Some(details) = &self.details {
+            value["details"] = details.clone();
+        }
+        value
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}（{}）", self.message, self.code)
+    }
+}
+
+impl From<ScreenshotError> for ToolError {
+    fn from(e: ScreenshotError) -> Self {
+        let error = Self::new(e.code(), e.message());
+        match e.details() {
+            Some(details) => error.with_details(details),
+            None => error,
+        }
+    }
+}
+
+impl From<ActionError> for ToolError {
+    fn from(e: ActionError) -> Self {
+        Self::new(e.code(), e.message())
+    }
+}
+
+/// 无参数工具的参数类型
+#[derive(Deserialize, JsonSchema)]
+pub struct NoArgs {}
+
+/// 调用入口：参数已通过 schema 校验
+pub type CallFn = Arc<
+    dyn for<'a> Fn(&'a Sender<TestMessage>, Value) -> BoxFuture<'a, Result<Value, ToolError>>
+        + Send
+        + Sync,
+>;
+
+/// 注册表中的一个工具
+pub struct ToolEntry {
+    name: String,
+    description: String,
+    stage: Option<TestStage>,
+    input_schema: Value,
+    output_schema: Option<Value>,
+    /// 游戏通过 register_test_tool 注册的工具可以被同名工具替换
+    custom: bool,
+    call: CallFn,
+}
+
+impl ToolEntry {
+    /// 校验参数后调用工具
+    pub async fn call(
+        &self,
+        sender: &Sender<TestMessage>,
+        args: &Value,
+    ) -> Result<Value, ToolError> {
+        schema::validate(&self.input_schema, args).map_err(|e| {
+            ToolError::invalid_argument(e.to_string()).with_details(json!({ "argument": e.path }))
+        })?;
+        (self.call)(sender, args.clone()).await
+    }
+}
+
+/// 按注册顺序保存的工具表
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<ToolEntry>>,
+}
+
+impl ToolRegistry {
+    pub fn register<T: Tool>(&mut self) -> &mut Self {
+        assert!(self.get(T::NAME).is_none(), "重复注册的工具: {}", T::NAME);
+        self.tools.push(Arc::new(ToolEntry {
+            name: T::NAME.to_string(),
+            description: T::DESCRIPTION.to_string(),
+            stage: T::STAGE,
+            input_schema: with_shared_args(generate_schema::<T::Args>()),
+            output_schema: Some(generate_schema::<T::Output>())
+                .filter(|schema| schema["type"] == "object"),
+            custom: false,
+            call: Arc::new(call_typed::<T>),
+        }));
+        self
+    }
+
+    /// 注册游戏自定义工具，替换同名的自定义工具；与内置工具重名时返回错误
+    pub fn register_custom(
+        &mut self,
+        name: String,
+        description: String,
+        stage: TestStage,
+        input_schema: Value,
+        call: CallFn,
+    ) -> Result<(), String> {
+        if !input_schema.is_object() {
+            return Err(format!("工具 {} 的 inputSchema 必须是对象", name));
+        }
+        let entry = Arc::new(ToolEntry {
+            input_schema: with_shared_args(input_schema),
+            output_schema: None,
+            name,
+            description,
+            stage: Some(stage),
+            custom: true,
+            call,
+        });
+        match self.tools.iter_mut().find(|tool| tool.name == entry.name) {
+            Some(tool) if tool.custom => *tool = entry,
+            Some(_) => return Err(format!("与内置工具重名: {}", entry.name)),
+            None => self.tools.push(entry),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<ToolEntry>> {
+        self.tools.iter().find(|tool| tool.name == name).cloned()
+    }
+
+    /// tools/list 的结果
+    pub fn list(&self) -> Value {
+        let tools: Vec<Value> = self
+            .tools
+            .iter()
+            .map(|tool| {
+                let mut value = json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "inputSchema": tool.input_schema,
+                });
+                if let Some(output_schema) = &tool.output_schema {
+                    value["outputSchema"] = output_schema.clone();
                 }
-            },
-            {
-                "name": "evaluate_script",
-                "description": "在 Tauri 前端 WebView 执行 JavaScript（需设置 JS_EVALUATOR_URL 环境变量）",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "script": { "type": "string", "description": "JavaScript 代码" }
-                    },
-                    "required": ["script"]
+                if let Some(stage) = tool.stage {
+                    value["_meta"] = json!({
+                        "stage": stage.name(),
+                        "schedule": stage.schedule()
+                    });
                 }
-            }
+                value
+            })
+            .collect();
+        json!({ "tools": tools })
+    }
+}
+
+fn call_typed<T: Tool>(
+    sender: &Sender<TestMessage>,
+    args: Value,
+) -> BoxFuture<'_, Result<Value, ToolError>> {
+    Box::pin(async move {
+        let args: T::Args = serde_json::from_value(args)
+            .map_err(|e| ToolError::invalid_argument(format!("参数错误: {}", e)))?;
+        let output = T::call(sender, args).await?;
+        serde_json::to_value(output)
+            .map_err(|e| ToolError::internal(format!("序列化结果失败: {}", e)))
+    })
+}
+
+/// 由参数或结果类型生成 inputSchema / outputSchema
+fn generate_schema<A: JsonSchema>() -> Value {
+    let mut schema = SchemaSettings::draft2020_12()
+        .into_generator()
+        .into_root_schema_for::<A>();
+    for key in ["$schema", "title", "description"] {
+        schema.remove(key);
+    }
+    schema.to_value()
+}
+
+/// 加上所有工具共享的 settle 与 timeout_ms 参数
+fn with_shared_args(mut schema: Value) -> Value {
+    if !schema["properties"].is_object() {
+        schema["properties"] = json!({});
+    }
+    schema["properties"]["settle"] = settle_schema();
+    schema["properties"]["timeout_ms"] = json!({
+        "description": "整个调用的超时（毫秒），替代默认的 30 秒（截图 10 秒）。超时或收到 notifications/cancelled 时游戏跳过尚未执行的命令",
+        "type": "integer",
+        "minimum": 1
+    });
+    schema
+}
+
+/// 所有工具共享的 settle 参数
+fn settle_schema() -> Value {
+    json!({
+        "description": "响应时机：immediate（默认，消息取出后立即返回）、update（本帧 Update 之后）、render（本帧渲染完成后）或整数 N（再等待 N 帧）。结果的 _meta.frame 为对应帧号",
+        "oneOf": [
+            { "type": "string", "enum": ["immediate", "update", "render"] },
+            { "type": "integer", "minimum": 0 }
         ]
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct EchoArgs {
+        /// 回显的文本
+        text: String,
+        #[serde(default = "default_times")]
+        #[schemars(range(min = 1))]
+        times: u32,
+    }
+
+    fn default_times() -> u32 {
+        1
+    }
+
+    #[derive(Serialize, JsonSchema)]
+    struct EchoOutput {
+        /// 重复后的文本
+        text: String,
+    }
+
+    impl Tool for Echo {
+        const NAME: &'static str = "echo";
+        const DESCRIPTION: &'static str = "回显";
+        const STAGE: Option<TestStage> = Some(TestStage::Query);
+        type Args = EchoArgs;
+        type Output = EchoOutput;
+
+        async fn call(_: &Sender<TestMessage>, args: EchoArgs) -> Result<EchoOutput, ToolError> {
+            Ok(EchoOutput {
+                text: args.text.repeat(args.times as usize),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let mut registry = ToolRegistry::default();
+        registry.register::<Echo>();
+        let list = registry.list();
+        let tool = &list["tools"][0];
+        assert_eq!(tool["name"], "echo");
+        assert_eq!(tool["_meta"]["stage"], "query");
+        let schema = &tool["inputSchema"];
+        assert_eq!(schema["required"], json!(["text"]));
+        assert_eq!(schema["properties"]["text"]["description"], "回显的文本");
+        assert_eq!(schema["properties"]["times"]["default"], 1);
+        assert!(schema["properties"]["settle"].is_object());
+        assert_eq!(schema["properties"]["timeout_ms"]["minimum"], 1);
+        assert!(schema.get("title").is_none());
+        let output_schema = &tool["outputSchema"];
+        assert_eq!(output_schema["type"], "object");
+        assert_eq!(output_schema["required"], json!(["text"]));
+
+        let (sender, _receiver) = crossbeam_channel::unbounded();
+        let echo = registry.get("echo").unwrap();
+        let args = json!({ "text": "ab", "times": 2, "settle": "render" });
+        assert_eq!(
+            echo.call(&sender, &args).await,
+            Ok(json!({ "text": "abab" }))
+        );
+        let missing = echo
+            .call(&sender, &json!({ "times": 2 }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code, "invalid_argument");
+        assert_eq!(missing.message, "缺少参数: text");
+        assert_eq!(missing.details, Some(json!({ "argument": "text" })));
+        let out_of_range = echo
+            .call(&sender, &json!({ "text": "a", "times": 0 }))
+            .await
+            .unwrap_err();
+        assert_eq!(out_of_range.message, "参数 times 不能小于 1");
+        assert!(registry.get("nope").is_none());
+
+        // 自定义工具不能覆盖内置工具，但可以替换自身
+        let custom = |answer: i64| -> CallFn {
+            Arc::new(move |_, _| Box::pin(async move { Ok(json!(answer)) }))
+        };
+        let schema = json!({ "type": "object" });
+        assert!(registry
+            .register_custom(
+                "echo".into(),
+                String::new(),
+                TestStage::Interaction,
+                schema.clone(),
+                custom(0)
+            )
+            .is_err());
+        for answer in [1, 2] {
+            registry
+                .register_custom(
+                    "answer".into(),
+                    "回答".into(),
+                    TestStage::Interaction,
+                    schema.clone(),
+                    custom(answer),
+                )
+                .unwrap();
+        }
+        assert_eq!(registry.list()["tools"].as_array().unwrap().len(), 2);
+        let answer = registry.get("answer").unwrap();
+        assert_eq!(answer.call(&sender, &json!({})).await, Ok(json!(2)));
+        assert!(answer
+            .call(&sender, &json!({ "settle": "later" }))
+            .await
+            .is_err());
+    }
+}
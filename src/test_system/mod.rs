@@ -1,8 +1,17 @@
+pub mod annotate;
 pub mod bevy_systems;
 pub mod channel;
+pub mod cursor;
+pub mod custom_tools;
+pub mod environment;
+pub mod highlight;
 pub mod mcp;
+pub mod plugin;
+pub mod recording;
+pub mod screenshot;
 pub mod server;
+pub mod visual_diff;
 
-pub use bevy_systems::receive_test_messages;
-pub use channel::{TestMessage, TEST_COMMAND_CHANNEL};
-pub use server::start_test_server;
+pub use custom_tools::RegisterTestTool;
+pub use plugin::TestSystemPlugin;
+pub use server::{start_stdio_server, start_test_server};
@@ -0,0 +1,99 @@
+//! 测试系统插件：注册命令处理系统并约定它们在帧内的执行顺序
+//!
+//! 一帧内的顺序：
+//! - PreUpdate  `TestSystems::Input`        输入采集之后、拾取与 UI 焦点之前：取出消息，注入键盘输入，登记帧同步屏障
+//! - PreUpdate  `TestSystems::Interaction`  UI 焦点之后：覆盖 Interaction、修改 UI，游戏的 Update 系统本帧即可看到
+//! - Last       `TestSystems::Query`        Update / PostUpdate 之后：快照、组件统计、截图，推进稳定截图与录制
+//! - Last       `TestSystems::Settle`       FrameCount 递增之后：返回满足条件的帧同步屏障
+
+use bevy::diagnostic::update_frame_count;
+use bevy::input::InputSystems;
+use bevy::picking::PickingSystems;
+use bevy::prelude::*;
+use bevy::render::{Render, RenderApp, RenderSystems};
+use bevy::ui::UiSystems;
+
+use crate::test_system::bevy_systems::{
+    apply_interaction_messages, apply_query_messages, receive_test_messages, resolve_settles,
+    PendingSettles, StagedTestMessages,
+};
+use crate::test_system::cursor::{update_virtual_cursor, VirtualCursor};
+use crate::test_system::environment::{log_environment, record_surface_format, SurfaceFormat};
+use crate::test_system::highlight::update_highlights;
+use crate::test_system::recording::{capture_recording_frames, Recording};
+use crate::test_system::screenshot::{advance_stable_captures, StableCaptures};
+
+/// 测试命令处理的系统集
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TestSystems {
+    Input,
+    Interaction,
+    Query,
+    Settle,
+}
+
+pub struct TestSystemPlugin;
+
+impl Plugin for TestSystemPlugin {
+    fn build(&self, app: &mut App) {
+        // 主窗口表面格式只在渲染子应用中可见，通过共享资源传回主世界
+        let surface_format = SurfaceFormat::default();
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(surface_format.clone())
+                .add_systems(Render, record_surface_format.in_set(RenderSystems::Queue));
+        }
+        app.insert_resource(surface_format)
+            .init_resource::<PendingSettles>()
+            .init_resource::<StagedTestMessages>()
+            .init_resource::<StableCaptures>()
+            .init_resource::<Recording>()
+            .init_resource::<VirtualCursor>()
+            .configure_sets(
+                PreUpdate,
+                (
+                    TestSystems::Input
+                        .after(InputSystems)
+                        .before(PickingSystems::ProcessInput)
+                        .before(UiSystems::Focus),
+                    TestSystems::Interaction
+                        .after(TestSystems::Input)
+                        .after(UiSystems::Focus),
+                ),
+            )
+            .configure_sets(
+                Last,
+                (
+                    TestSystems::Query,
+                    TestSystems::Settle
+                        .after(TestSystems::Query)
+                        .after(update_frame_count),
+                ),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    receive_test_messages.in_set(TestSystems::Input),
+                    apply_interaction_messages.in_set(TestSystems::Interaction),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (update_highlights, update_virtual_cursor).before(UiSystems::Layout),
+            )
+            .add_systems(
+                Last,
+                (
+                    (
+                        apply_query_messages,
+                        advance_stable_captures,
+                        capture_recording_frames,
+                    )
+                        .chain()
+                        .in_set(TestSystems::Query),
+                    resolve_settles.in_set(TestSystems::Settle),
+                    log_environment.after(TestSystems::Settle),
+                ),
+            );
+    }
+}
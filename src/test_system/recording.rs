@@ -0,0 +1,331 @@
+//! 场景录制：每 N 帧截取一次到内存，停止时编码为 APNG 或 GIF
+
+use bevy::diagnostic::FrameCount;
+use bevy::prelude::*;
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::test_system::channel::{
+    RecordingFormat, RecordingOptions, RecordingSummary, ScreenshotError,
+};
+use crate::test_system::screenshot;
+
+/// 停止录制时等待下一个在途截图完成的最长时间（截图一直没有完成时的兜底）
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 进行中的录制（同一时间只有一个）
+#[derive(Resource, Default)]
+pub struct Recording(Option<ActiveRecording>);
+
+pub struct ActiveRecording {
+    options: RecordingOptions,
+    started: Instant,
+    start_frame: u32,
+    /// 截图回调在后台线程写入
+    frames: Arc<Mutex<RecordedFrames>>,
+    /// 已请求的截图数
+    requested: u32,
+    /// 每个截图回调结束时（无论成功与否）发送一次完成通知
+    done_tx: Sender<()>,
+    done_rx: Receiver<()>,
+}
+
+#[derive(Default)]
+struct RecordedFrames {
+    frames: VecDeque<RecordedFrame>,
+    dropped: u32,
+}
+
+struct RecordedFrame {
+    /// 自录制开始经过的帧数，用于恢复乱序到达的帧
+    frame: u32,
+    /// 截图请求时刻，用于计算帧间隔
+    elapsed: Duration,
+    image: RgbImage,
+}
+
+/// 开始录制
+pub fn start(world: &mut World, options: RecordingOptions) -> Result<(), String> {
+    let start_frame = world.resource::<FrameCount>().0;
+    let mut recording = world.resource_mut::<Recording>();
+    if recording.0.is_some() {
+        return Err("已经在录制中".to_string());
+    }
+    info!(
+        "开始录制: 每 {} 帧一次，{:?}",
+        options.every_n_frames, options.format
+    );
+    let (done_tx, done_rx) = crossbeam_channel::unbounded();
+    recording.0 = Some(ActiveRecording {
+        options,
+        started: Instant::now(),
+        start_frame,
+        frames: default(),
+        requested: 0,
+        done_tx,
+        done_rx,
+    });
+    Ok(())
+}
+
+/// 停止录制：在后台线程等待在途截图后编码，写入 path（None 时丢弃）
+pub fn stop(
+    world: &mut World,
+    path: Option<String>,
+    response: oneshot::Sender<Result<RecordingSummary, ScreenshotError>>,
+) {
+    let Some(recording) = world.resource_mut::<Recording>().0.take() else {
+        let _ = response.send(Err(ScreenshotError::NotRecording(
+            "当前没有进行中的录制".to_string(),
+        )));
+        return;
+    };
+    std::thread::spawn(move || {
+        let result = finish(recording, path);
+        match &result {
+            Ok(summary) => info!(
+                "录制结束: {} 帧，{}",
+                summary.frames,
+                summary.path.as_deref().unwrap_or("已丢弃")
+            ),
+            Err(e) => warn!("录制失败: {}", e.message()),
+        }
+        let _ = response.send(result);
+    });
+}
+
+fn finish(
+    recording: ActiveRecording,
+    path: Option<String>,
+) -> Result<RecordingSummary, ScreenshotError> {
+    let ActiveRecording {
+        options,
+        frames,
+        requested,
+        done_tx,
+        done_rx,
+        ..
+    } = recording;
+    // 等所有已请求的截图完成后再编码；回调未执行就被丢弃时发送端随之释放，通道断开
+    drop(done_tx);
+    let mut completed = 0;
+    while completed < requested {
+        match done_rx.recv_timeout(FLUSH_TIMEOUT) {
+            Ok(()) => completed += 1,
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                warn!("等待在途截图超时，已完成 {}/{}", completed, requested);
+                break;
+            }
+        }
+    }
+    let recorded = std::mem::take(&mut *frames.lock().unwrap());
+    let mut frames: Vec<RecordedFrame> = recorded.frames.into();
+    frames.sort_by_key(|f| f.frame);
+    let Some(first) = frames.first() else {
+        // 丢弃录制时没有帧也不算失败
+        if path.is_none() {
+            return Ok(RecordingSummary::default());
+        }
+        return Err(ScreenshotError::NotRecording(
+            "录制中没有捕获到帧".to_string(),
+        ));
+    };
+    let (width, height) = first.image.dimensions();
+    // 窗口尺寸变化后的帧无法放进同一动画，直接丢弃
+    frames.retain(|f| f.image.dimensions() == (width, height));
+    let duration_ms = frames.last().map_or(0, |last| {
+        (last.elapsed - frames[0].elapsed).as_millis() as u64
+    });
+
+    let mut summary = RecordingSummary {
+        path: None,
+        frames: frames.len() as u32,
+        dropped: recorded.dropped,
+        width,
+        height,
+        duration_ms,
+        bytes: 0,
+    };
+    let Some(mut path) = path else {
+        return Ok(summary);
+    };
+    let format = options.format;
+    if std::path::Path::new(&path).extension().is_none() {
+        path = format!("{}.{}", path, format.extension());
+    }
+    let bytes = match format {
+        RecordingFormat::Apng => encode_apng(&frames)?,
+        RecordingFormat::Gif => encode_gif(&frames)?,
+    };
+    screenshot::write_file(&path, &bytes)?;
+    summary.bytes = bytes.len();
+    summary.path = Some(path);
+    Ok(summary)
+}
+
+// 录制中每 N 帧请求一次截图，图像缩放后存入内存
+pub fn capture_recording_frames(world: &mut World) {
+    let current = world.resource::<FrameCount>().0;
+    let mut recording = world.resource_mut::<Recording>();
+    let Some(recording) = recording.0.as_mut() else {
+        return;
+    };
+    let frame = current.wrapping_sub(recording.start_frame);
+    if frame % recording.options.every_n_frames.max(1) != 0 {
+        return;
+    }
+    recording.requested += 1;
+    let elapsed = recording.started.elapsed();
+    let frames = recording.frames.clone();
+    let options = recording.options.clone();
+    let done = recording.done_tx.clone();
+    screenshot::capture(
+        world,
+        Box::new(move |result| {
+            if let Ok(img) = result {
+                let image = screenshot::downscale(img, options.scale, options.max_width).to_rgb8();
+                let mut recorded = frames.lock().unwrap();
+                if recorded.frames.len() >= options.max_frames.max(1) {
+                    recorded.frames.pop_front();
+                    recorded.dropped += 1;
+                }
+                recorded.frames.push_back(RecordedFrame {
+                    frame,
+                    elapsed,
+                    image,
+                });
+            }
+            let _ = done.send(());
+        }),
+    );
+}
+
+/// 每帧的显示时长（毫秒）：到下一帧的间隔，最后一帧沿用前一个间隔
+fn frame_delays_ms(frames: &[RecordedFrame]) -> Vec<u32> {
+    let mut delays: Vec<u32> = frames
+        .windows(2)
+        .map(|w| (w[1].elapsed - w[0].elapsed).as_millis().max(1) as u32)
+        .collect();
+    delays.push(delays.last().copied().unwrap_or(100));
+    delays
+}
+
+fn encode_apng(frames: &[RecordedFrame]) -> Result<Vec<u8>, ScreenshotError> {
+    let encode_err = |e: png::EncodingError| ScreenshotError::Encode(e.to_string());
+    let (width, height) = frames[0].image.dimensions();
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(encode_err)?;
+    let mut writer = encoder.write_header().map_err(encode_err)?;
+    for (frame, delay) in frames.iter().zip(frame_delays_ms(frames)) {
+        writer
+            .set_frame_delay(delay.min(u16::MAX as u32) as u16, 1000)
+            .map_err(encode_err)?;
+        writer
+            .write_image_data(frame.image.as_raw())
+            .map_err(encode_err)?;
+    }
+    writer.finish().map_err(encode_err)?;
+    Ok(bytes)
+}
+
+fn encode_gif(frames: &[RecordedFrame]) -> Result<Vec<u8>, ScreenshotError> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut bytes, 10);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| ScreenshotError::Encode(e.to_string()))?;
+        let gif_frames = frames
+            .iter()
+            .zip(frame_delays_ms(frames))
+            .map(|(frame, delay)| {
+                let rgba = DynamicImage::ImageRgb8(frame.image.clone()).to_rgba8();
+                Frame::from_parts(rgba, 0, 0, Delay::from_numer_denom_ms(delay, 1))
+            });
+        encoder
+            .encode_frames(gif_frames)
+            .map_err(|e| ScreenshotError::Encode(e.to_string()))?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+
+    fn sample_frames() -> Vec<RecordedFrame> {
+        (0..3u8)
+            .map(|i| RecordedFrame {
+                frame: i as u32 * 2,
+                elapsed: Duration::from_millis(i as u64 * 40),
+                image: RgbImage::from_pixel(8, 6, image::Rgb([i * 80, 0, 0])),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_animations() {
+        let frames = sample_frames();
+        assert_eq!(frame_delays_ms(&frames), vec![40, 40, 40]);
+
+        let apng = encode_apng(&frames).expect("APNG 编码失败");
+        let decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(apng)).unwrap();
+        assert!(decoder.is_apng().unwrap());
+        assert_eq!(decoder.apng().unwrap().into_frames().count(), 3);
+
+        let gif = encode_gif(&frames).expect("GIF 编码失败");
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(gif)).unwrap();
+        assert_eq!(decoder.into_frames().count(), 3);
+    }
+
+    fn active_recording() -> ActiveRecording {
+        let (done_tx, done_rx) = crossbeam_channel::unbounded();
+        ActiveRecording {
+            options: RecordingOptions {
+                every_n_frames: 1,
+                format: RecordingFormat::Apng,
+                scale: None,
+                max_width: None,
+                max_frames: 10,
+            },
+            started: Instant::now(),
+            start_frame: 0,
+            frames: default(),
+            requested: 0,
+            done_tx,
+            done_rx,
+        }
+    }
+
+    #[test]
+    fn test_finish_waits_for_captures() {
+        // 没有帧时丢弃录制不算失败，保存则报错
+        assert_eq!(finish(active_recording(), None).unwrap().frames, 0);
+        assert!(finish(active_recording(), Some("unused.png".to_string())).is_err());
+
+        // 在途截图完成后才编码
+        let mut recording = active_recording();
+        recording.requested = 1;
+        let frames = recording.frames.clone();
+        let done = recording.done_tx.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            frames.lock().unwrap().frames.extend(sample_frames().pop());
+            let _ = done.send(());
+        });
+        assert_eq!(finish(recording, None).unwrap().frames, 1);
+    }
+}
@@ -0,0 +1,492 @@
+//! 基于 Bevy ScreenshotCaptured 事件的截图：图像到达内存后才响应
+
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use log::{info, warn};
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::headless::OffscreenTarget;
+use crate::test_system::annotate::{self, Annotation};
+use crate::test_system::channel::{
+    ColorStats, ElementImage, EncodedImage, ImageEncoding, ProbeResult, ProbeTarget,
+    ScreenshotError, ScreenshotOptions, Stability, StableOptions,
+};
+
+type EncodedResponse = oneshot::Sender<Result<EncodedImage, ScreenshotError>>;
+
+/// 截图完成回调：在后台线程中拿到 RGB 图像
+pub type CaptureCallback = Box<dyn FnOnce(Result<DynamicImage, ScreenshotError>) + Send + Sync>;
+
+/// 截取当前渲染目标（无窗口模式下为离屏 Image，否则为主窗口）
+///
+/// 图像到达内存后在后台线程调用 `on_captured`，避免编码阻塞主循环
+pub fn capture(world: &mut World, on_captured: CaptureCallback) {
+    let screenshot = match world.get_resource::<OffscreenTarget>() {
+        Some(target) => Screenshot::image(target.0.clone()),
+        None => Screenshot::primary_window(),
+    };
+    // observer 是 FnMut，回调只会被调用一次
+    let mut on_captured = Some(on_captured);
+    world
+        .spawn(screenshot)
+        .observe(move |captured: On<ScreenshotCaptured>| {
+            let Some(on_captured) = on_captured.take() else {
+                return;
+            };
+            let image = captured.image.clone();
+            std::thread::spawn(move || {
+                // 丢弃 alpha 通道（HDR 下存放亮度），与 save_to_disk 保持一致
+                let result = image
+                    .try_into_dynamic()
+                    .map(|img| DynamicImage::ImageRgb8(img.to_rgb8()))
+                    .map_err(|e| ScreenshotError::Convert(e.to_string()));
+                on_captured(result);
+            });
+        });
+}
+
+/// 截图后按选项缩放、编码，可选写入文件，通过 response 返回编码后的图像
+///
+/// 指定 `stable` 时登记为稳定截图，由 `advance_stable_captures` 逐帧推进；
+/// 指定 annotation 时在缩放后的图像上绘制编号框
+pub fn capture_encoded(
+    world: &mut World,
+    options: ScreenshotOptions,
+    annotation: Option<Annotation>,
+    response: EncodedResponse,
+) {
+    let stable = options.stable;
+    let output = Arc::new(Output {
+        options,
+        annotation,
+    });
+    if let Some(stable) = stable {
+        let deadline = Instant::now() + Duration::from_millis(stable.timeout_ms);
+        world
+            .resource_mut::<StableCaptures>()
+            .0
+            .push(StableCapture {
+                output,
+                stable,
+                deadline,
+                next_seq: 0,
+                state: Arc::new(Mutex::new(StableState {
+                    response: Some(response),
+                    ..default()
+                })),
+            });
+        return;
+    }
+    capture(
+        world,
+        Box::new(move |result| {
+            respond(response, result.and_then(|img| output.finish(img, None)));
+        }),
+    );
+}
+
+/// 截图的后处理选项
+struct Output {
+    options: ScreenshotOptions,
+    annotation: Option<Annotation>,
+}
+
+impl Output {
+    /// 缩放、标注、编码并按需写入文件
+    fn finish(
+        &self,
+        img: DynamicImage,
+        stability: Option<Stability>,
+    ) -> Result<EncodedImage, ScreenshotError> {
+        let full_width = img.width();
+        let mut img = downscale(img, self.options.scale, self.options.max_width);
+        if let Some(annotation) = &self.annotation {
+            let mut rgb = img.to_rgb8();
+            let scale = rgb.width() as f32 / full_width as f32;
+            annotate::draw(&mut rgb, annotation, scale);
+            img = DynamicImage::ImageRgb8(rgb);
+        }
+        let mut encoded = encode(&img, self.options.encoding)?;
+        encoded.stability = stability;
+        if let Some(annotation) = &self.annotation {
+            encoded.marks = annotation.marks.clone();
+        }
+        if let Some(path) = &self.options.path {
+            write_file(path, &encoded.bytes)?;
+            info!("截图已保存: {}", path);
+        }
+        Ok(encoded)
+    }
+}
+
+fn respond(response: EncodedResponse, result: Result<EncodedImage, ScreenshotError>) {
+    if let Err(e) = &result {
+        warn!("截图失败: {}", e.message());
+    }
+    let _ = response.send(result);
+}
+
+/// 进行中的稳定截图
+#[derive(Resource, Default)]
+pub struct StableCaptures(Vec<StableCapture>);
+
+pub struct StableCapture {
+    output: Arc<Output>,
+    stable: StableOptions,
+    deadline: Instant,
+    /// 下一次截图的序号，用于丢弃乱序到达的旧帧
+    next_seq: u32,
+    state: Arc<Mutex<StableState>>,
+}
+
+/// 稳定截图在后台线程间共享的状态，response 被取走即表示已完成
+#[derive(Default)]
+struct StableState {
+    latest: Option<(u32, DynamicImage)>,
+    /// 以 latest 结尾的连续一致帧数
+    matching: u32,
+    captured: u32,
+    response: Option<EncodedResponse>,
+}
+
+// 稳定截图：每帧截取一次，与上一帧比较，连续一致的帧数达到要求或超时后返回
+pub fn advance_stable_captures(world: &mut World) {
+    let captures = std::mem::take(&mut world.resource_mut::<StableCaptures>().0);
+    let mut pending = Vec::with_capacity(captures.len());
+    for mut job in captures {
+        let mut state = job.state.lock().unwrap();
+        let Some(response) = state.response.take() else {
+            continue;
+        };
+        if response.is_closed() {
+            info!("稳定截图已取消，已比较 {} 帧", state.captured);
+            continue;
+        }
+        if Instant::now() >= job.deadline {
+            let latest = state.latest.take();
+            let captured = state.captured;
+            drop(state);
+            warn!("等待画面稳定超时，已比较 {} 帧", captured);
+            let output = job.output;
+            std::thread::spawn(move || {
+                let result = match latest {
+                    Some((_, img)) => output.finish(
+                        img,
+                        Some(Stability {
+                            stable: false,
+                            frames_captured: captured,
+                        }),
+                    ),
+                    None => Err(ScreenshotError::Timeout("超时前未捕获到画面".to_string())),
+                };
+                respond(response, result);
+            });
+            continue;
+        }
+        state.response = Some(response);
+        drop(state);
+
+        let seq = job.next_seq;
+        job.next_seq += 1;
+        let (state, output, stable) = (job.state.clone(), job.output.clone(), job.stable);
+        capture(
+            world,
+            Box::new(move |result| {
+                let mut state = state.lock().unwrap();
+                if state.response.is_none() {
+                    return;
+                }
+                let img = match result {
+                    Ok(img) => img,
+                    Err(e) => {
+                        let response = state.response.take().unwrap();
+                        drop(state);
+                        respond(response, Err(e));
+                        return;
+                    }
+                };
+                if state
+                    .latest
+                    .as_ref()
+                    .is_some_and(|(latest, _)| *latest > seq)
+                {
+                    return;
+                }
+                state.captured += 1;
+                let matched = state
+                    .latest
+                    .as_ref()
+                    .is_some_and(|(_, prev)| frames_match(prev, &img, stable.tolerance));
+                state.matching = if matched { state.matching + 1 } else { 1 };
+                if state.matching < stable.frames.max(1) {
+                    state.latest = Some((seq, img));
+                    return;
+                }
+                let response = state.response.take().unwrap();
+                let stability = Stability {
+                    stable: true,
+                    frames_captured: state.captured,
+                };
+                drop(state);
+                respond(response, output.finish(img, Some(stability)));
+            }),
+        );
+        pending.push(job);
+    }
+    world.resource_mut::<StableCaptures>().0.extend(pending);
+}
+
+/// 两帧是否在容差内一致（逐通道差值均不超过 tolerance）
+fn frames_match(a: &DynamicImage, b: &DynamicImage, tolerance: u8) -> bool {
+    match (a.as_rgb8(), b.as_rgb8()) {
+        (Some(a), Some(b)) if a.dimensions() == b.dimensions() => a
+            .as_raw()
+            .iter()
+            .zip(b.as_raw())
+            .all(|(x, y)| x.abs_diff(*y) <= tolerance),
+        _ => false,
+    }
+}
+
+/// 待裁剪的元素区域（物理像素）
+pub struct ElementRegion {
+    pub selector: String,
+    pub uid: String,
+    pub rect: Rect,
+}
+
+/// 截图一次，按各元素区域裁剪并编码
+pub fn capture_elements(
+    world: &mut World,
+    regions: Vec<ElementRegion>,
+    encoding: ImageEncoding,
+    response: oneshot::Sender<Result<Vec<ElementImage>, ScreenshotError>>,
+) {
+    capture(
+        world,
+        Box::new(move |result| {
+            let result = result.and_then(|img| {
+                regions
+                    .into_iter()
+                    .map(|region| {
+                        let (x, y, width, height) =
+                            clamp_rect(region.rect, &img).ok_or_else(|| {
+                                ScreenshotError::NotFound {
+                                    message: format!("元素不在画面内: {}", region.selector),
+                                    unmatched: vec![region.selector.clone().into()],
+                                }
+                            })?;
+                        let cropped = img.crop_imm(x, y, width, height);
+                        Ok(ElementImage {
+                            selector: region.selector,
+                            uid: region.uid,
+                            x,
+                            y,
+                            width,
+                            height,
+                            image: encode(&cropped, encoding)?,
+                        })
+                    })
+                    .collect()
+            });
+            if let Err(e) = &result {
+                warn!("元素截图失败: {}", e.message());
+            }
+            let _ = response.send(result);
+        }),
+    );
+}
+
+/// 截图一次，统计各探测区域的颜色（区域已解析为物理像素矩形）
+pub fn capture_probe(
+    world: &mut World,
+    regions: Vec<(ProbeTarget, Rect)>,
+    response: oneshot::Sender<Result<Vec<ProbeResult>, ScreenshotError>>,
+) {
+    capture(
+        world,
+        Box::new(move |result| {
+            let result = result.and_then(|img| {
+                let rgb = img.to_rgb8();
+                regions
+                    .into_iter()
+                    .map(|(target, rect)| {
+                        let (x, y, width, height) =
+                            clamp_rect(rect, &img).ok_or_else(|| ScreenshotError::NotFound {
+                                message: format!("探测区域不在画面内: {}", target),
+                                unmatched: vec![serde_json::json!(target)],
+                            })?;
+                        Ok(ProbeResult {
+                            target,
+                            x,
+                            y,
+                            width,
+                            height,
+                            stats: color_stats(&rgb, x, y, width, height),
+                        })
+                    })
+                    .collect()
+            });
+            if let Err(e) = &result {
+                warn!("像素探测失败: {}", e.message());
+            }
+            let _ = response.send(result);
+        }),
+    );
+}
+
+/// 统计区域内的颜色：平均、最小、最大与中位数
+fn color_stats(img: &RgbImage, x: u32, y: u32, width: u32, height: u32) -> ColorStats {
+    let mut channels: [Vec<u8>; 3] = Default::default();
+    for py in y..y + height {
+        for px in x..x + width {
+            let pixel = img.get_pixel(px, py);
+            for (c, values) in channels.iter_mut().enumerate() {
+                values.push(pixel[c]);
+            }
+        }
+    }
+    let mut stats = ColorStats {
+        pixels: width * height,
+        ..default()
+    };
+    for (c, values) in channels.iter_mut().enumerate() {
+        values.sort_unstable();
+        stats.avg[c] = values.iter().map(|&v| v as f32).sum::<f32>() / values.len() as f32;
+        stats.min[c] = values[0];
+        stats.max[c] = values[values.len() - 1];
+        stats.median[c] = values[values.len() / 2];
+    }
+    stats
+}
+
+/// 把矩形限制在图像范围内，返回 (x, y, width, height)；完全在画面外时返回 None
+fn clamp_rect(rect: Rect, img: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let bounds = Rect::new(0.0, 0.0, img.width() as f32, img.height() as f32);
+    let rect = rect.intersect(bounds);
+    let (x0, y0) = (rect.min.x.floor() as u32, rect.min.y.floor() as u32);
+    let (x1, y1) = (rect.max.x.ceil() as u32, rect.max.y.ceil() as u32);
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// 按 scale 与 max_width 等比缩小（不放大）
+pub fn downscale(img: DynamicImage, scale: Option<f32>, max_width: Option<u32>) -> DynamicImage {
+    let mut width = img.width() as f32 * scale.unwrap_or(1.0).clamp(0.0, 1.0);
+    if let Some(max_width) = max_width {
+        width = width.min(max_width as f32);
+    }
+    let width = (width.round() as u32).max(1);
+    if width >= img.width() {
+        return img;
+    }
+    let height = ((img.height() as f32 * width as f32 / img.width() as f32).round() as u32).max(1);
+    img.resize_exact(width, height, FilterType::Triangle)
+}
+
+/// 按指定格式编码
+pub fn encode(
+    img: &DynamicImage,
+    encoding: ImageEncoding,
+) -> Result<EncodedImage, ScreenshotError> {
+    let mut bytes = Vec::new();
+    let result = match encoding {
+        ImageEncoding::Png => img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png),
+        ImageEncoding::Jpeg { quality } => img.write_with_encoder(JpegEncoder::new_with_quality(
+            &mut bytes,
+            quality.clamp(1, 100),
+        )),
+        ImageEncoding::WebP => img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::WebP),
+    };
+    result.map_err(|e| ScreenshotError::Encode(e.to_string()))?;
+    Ok(EncodedImage {
+        bytes,
+        mime_type: encoding.mime_type(),
+        width: img.width(),
+        height: img.height(),
+        stability: None,
+        marks: Vec::new(),
+    })
+}
+
+/// 写入文件（自动创建父目录）
+pub fn write_file(path: &str, bytes: &[u8]) -> Result<(), ScreenshotError> {
+    let path_ref = std::path::Path::new(path);
+    if let Some(parent) = path_ref.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| ScreenshotError::Write(format!("{}: {}", path, e)))?;
+    }
+    std::fs::write(path_ref, bytes).map_err(|e| ScreenshotError::Write(format!("{}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downscale_keeps_aspect_ratio() {
+        let img = DynamicImage::new_rgb8(800, 600);
+        let scaled = downscale(img.clone(), Some(0.5), None);
+        assert_eq!((scaled.width(), scaled.height()), (400, 300));
+
+        let limited = downscale(img.clone(), None, Some(200));
+        assert_eq!((limited.width(), limited.height()), (200, 150));
+
+        let unchanged = downscale(img, None, Some(1600));
+        assert_eq!((unchanged.width(), unchanged.height()), (800, 600));
+    }
+
+    #[test]
+    fn test_clamp_rect_to_image_bounds() {
+        let img = DynamicImage::new_rgb8(800, 600);
+        let inside = clamp_rect(Rect::new(10.0, 20.0, 110.0, 70.0), &img);
+        assert_eq!(inside, Some((10, 20, 100, 50)));
+
+        let partial = clamp_rect(Rect::new(-10.0, 580.0, 20.0, 640.0), &img);
+        assert_eq!(partial, Some((0, 580, 20, 20)));
+
+        let outside = clamp_rect(Rect::new(900.0, 0.0, 950.0, 10.0), &img);
+        assert_eq!(outside, None);
+    }
+
+    #[test]
+    fn test_frames_match_within_tolerance() {
+        let a = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([100; 3])));
+        let b = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(4, 4, image::Rgb([102; 3])));
+        assert!(frames_match(&a, &a, 0));
+        assert!(!frames_match(&a, &b, 1));
+        assert!(frames_match(&a, &b, 2));
+        assert!(!frames_match(&a, &DynamicImage::new_rgb8(4, 2), 255));
+    }
+
+    #[test]
+    fn test_color_stats() {
+        let mut img = RgbImage::from_pixel(4, 4, image::Rgb([77, 128, 179]));
+        img.put_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let stats = color_stats(&img, 0, 0, 3, 3);
+        assert_eq!(stats.pixels, 9);
+        assert_eq!(stats.median, [77, 128, 179]);
+        assert_eq!(stats.min, [77, 128, 179]);
+        assert_eq!(stats.max, [255, 255, 255]);
+        assert!((stats.avg[0] - (77.0 * 8.0 + 255.0) / 9.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_encode_formats() {
+        let img = DynamicImage::new_rgb8(4, 4);
+        for encoding in [
+            ImageEncoding::Png,
+            ImageEncoding::Jpeg { quality: 80 },
+            ImageEncoding::WebP,
+        ] {
+            let encoded = encode(&img, encoding).expect("编码失败");
+            assert_eq!(encoded.mime_type, encoding.mime_type());
+            assert!(!encoded.bytes.is_empty());
+        }
+    }
+}
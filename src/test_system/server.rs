@@ -3,15 +3,50 @@ use axum::{
     routing::{get, post},
     Router,
 };
-use crossbeam_channel::unbounded;
+use crossbeam_channel::{unbounded, Sender};
 use log::info;
+use std::future::Future;
 
 use crate::test_system::{
-    channel::{TestChannel, TEST_COMMAND_CHANNEL},
-    mcp::mcp_handler,
+    channel::{TestChannel, TestMessage, TEST_COMMAND_CHANNEL},
+    mcp::{mcp_handler, mcp_stream_handler, serve_stdio, watch_resources},
 };
 
+/// 启动 HTTP 测试服务器（MCP Streamable HTTP）
 pub fn start_test_server() {
+    spawn_server(|sender| async move {
+        let app = Router::new()
+            .route("/health", get(health_check))
+            .route("/mcp", post(mcp_handler).get(mcp_stream_handler))
+            .with_state(sender);
+
+        let port = std::env::var("TEST_PORT")
+            .ok()
+            .and_then(|p| p.parse::<u16>().ok())
+            .unwrap_or(9222);
+        let addr = format!("127.0.0.1:{}", port);
+        let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+
+        info!("测试服务器: http://{}", addr);
+        info!("MCP:        http://{}/mcp", addr);
+        axum::serve(listener, app).await.unwrap();
+    });
+}
+
+/// 以 stdio 传输提供 MCP，stdin 关闭后游戏正常退出
+pub fn start_stdio_server() {
+    spawn_server(|sender| async move {
+        info!("MCP:        stdio");
+        serve_stdio(sender).await;
+    });
+}
+
+/// 在独立线程的 tokio 运行时中建立命令通道并运行服务
+fn spawn_server<F, Fut>(serve: F)
+where
+    F: FnOnce(Sender<TestMessage>) -> Fut + Send + 'static,
+    Fut: Future<Output = ()>,
+{
     std::thread::spawn(|| {
         let runtime = tokio::runtime::Runtime::new().unwrap();
         runtime.block_on(async {
@@ -21,21 +56,9 @@ pub fn start_test_server() {
                 receiver,
             });
 
-            let app = Router::new()
-                .route("/health", get(health_check))
-                .route("/mcp", post(mcp_handler))
-                .with_state(sender);
-
-            let port = std::env::var("TEST_PORT")
-                .ok()
-                .and_then(|p| p.parse::<u16>().ok())
-                .unwrap_or(9222);
-            let addr = format!("127.0.0.1:{}", port);
-            let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-
-            info!("测试服务器: http://{}", addr);
-            info!("MCP:        http://{}/mcp", addr);
-            axum::serve(listener, app).await.unwrap();
+            tokio::spawn(watch_resources(sender.clone()));
+
+            serve(sender).await;
         });
     });
 }
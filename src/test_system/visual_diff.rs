@@ -0,0 +1,301 @@
+//! 视觉回归对比：比较截图与基准图（逐像素或 SSIM），支持忽略区域，生成高亮差异图
+
+use image::{Rgb, RgbImage};
+
+/// SSIM 计算窗口边长（像素）
+const SSIM_WINDOW: u32 = 8;
+
+/// 判定方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffMetric {
+    /// 差异像素占比不超过 max_diff_ratio
+    #[default]
+    Pixel,
+    /// 平均 SSIM 不低于 ssim_threshold（对抗锯齿等细微差异不敏感）
+    Ssim,
+}
+
+/// 对比参数
+#[derive(Clone, Copy, Debug)]
+pub struct DiffOptions {
+    /// 单通道允许的最大差值（0-255）
+    pub tolerance: u8,
+    /// 允许的差异像素占比（0-1）
+    pub max_diff_ratio: f64,
+    pub metric: DiffMetric,
+    /// SSIM 判定阈值（0-1）
+    pub ssim_threshold: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 2,
+            max_diff_ratio: 0.001,
+            metric: DiffMetric::Pixel,
+            ssim_threshold: 0.98,
+        }
+    }
+}
+
+/// 忽略区域（像素坐标）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaskRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 对比结果
+pub struct DiffResult {
+    pub passed: bool,
+    pub diff_pixels: u64,
+    /// 参与比较的像素数（不含忽略区域）
+    pub total_pixels: u64,
+    pub masked_pixels: u64,
+    pub diff_ratio: f64,
+    /// 所有像素中最大的单通道差值
+    pub max_channel_delta: u8,
+    /// 忽略区域之外的平均 SSIM（亮度通道）
+    pub ssim: f64,
+    /// 差异图：基准图变暗为灰度，差异像素标红，忽略区域标蓝
+    pub diff_image: RgbImage,
+}
+
+/// 比较两张同尺寸图像，masks 内的像素不参与比较；尺寸不同时返回 Err
+pub fn compare(
+    actual: &RgbImage,
+    baseline: &RgbImage,
+    options: DiffOptions,
+    masks: &[MaskRect],
+) -> Result<DiffResult, String> {
+    if actual.dimensions() != baseline.dimensions() {
+        return Err(format!(
+            "尺寸不一致: 实际 {}x{}，基准 {}x{}",
+            actual.width(),
+            actual.height(),
+            baseline.width(),
+            baseline.height()
+        ));
+    }
+
+    let mask = mask_bitmap(actual.width(), actual.height(), masks);
+    let mut diff_image = RgbImage::new(actual.width(), actual.height());
+    let mut diff_pixels = 0u64;
+    let mut masked_pixels = 0u64;
+    let mut max_channel_delta = 0u8;
+    for (((a, b), out), masked) in actual
+        .pixels()
+        .zip(baseline.pixels())
+        .zip(diff_image.pixels_mut())
+        .zip(&mask)
+    {
+        if *masked {
+            masked_pixels += 1;
+            *out = Rgb([90, 110, 200]);
+            continue;
+        }
+        let delta = (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0);
+        max_channel_delta = max_channel_delta.max(delta);
+        *out = if delta > options.tolerance {
+            diff_pixels += 1;
+            Rgb([255, 0, 0])
+        } else {
+            dimmed(b)
+        };
+    }
+
+    let total_pixels = u64::from(actual.width()) * u64::from(actual.height()) - masked_pixels;
+    let diff_ratio = if total_pixels == 0 {
+        0.0
+    } else {
+        diff_pixels as f64 / total_pixels as f64
+    };
+    let ssim = mean_ssim(actual, baseline, &mask);
+    let passed = match options.metric {
+        DiffMetric::Pixel => diff_ratio <= options.max_diff_ratio,
+        DiffMetric::Ssim => ssim >= options.ssim_threshold,
+    };
+    Ok(DiffResult {
+        passed,
+        diff_pixels,
+        total_pixels,
+        masked_pixels,
+        diff_ratio,
+        max_channel_delta,
+        ssim,
+        diff_image,
+    })
+}
+
+/// 展开忽略区域为逐像素标记（true 表示忽略）
+fn mask_bitmap(width: u32, height: u32, masks: &[MaskRect]) -> Vec<bool> {
+    let mut bitmap = vec![false; (width as usize) * (height as usize)];
+    for m in masks {
+        let x1 = m.x.saturating_add(m.width).min(width);
+        let y1 = m.y.saturating_add(m.height).min(height);
+        for y in m.y.min(height)..y1 {
+            let row = (y as usize) * (width as usize);
+            bitmap[row + m.x.min(width) as usize..row + x1 as usize].fill(true);
+        }
+    }
+    bitmap
+}
+
+/// 按 SSIM_WINDOW 分块计算亮度 SSIM 并取平均，忽略区域内的像素不参与统计
+fn mean_ssim(actual: &RgbImage, baseline: &RgbImage, mask: &[bool]) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = actual.dimensions();
+    let mut sum = 0.0;
+    let mut windows = 0u32;
+    for wy in (0..height).step_by(SSIM_WINDOW as usize) {
+        for wx in (0..width).step_by(SSIM_WINDOW as usize) {
+            let mut samples = Vec::new();
+            for y in wy..(wy + SSIM_WINDOW).min(height) {
+                for x in wx..(wx + SSIM_WINDOW).min(width) {
+                    if !mask[(y as usize) * (width as usize) + x as usize] {
+                        samples
+                            .push((luma(actual.get_pixel(x, y)), luma(baseline.get_pixel(x, y))));
+                    }
+                }
+            }
+            if samples.is_empty() {
+                continue;
+            }
+            let n = samples.len() as f64;
+            let mean_a = samples.iter().map(|s| s.0).sum::<f64>() / n;
+            let mean_b = samples.iter().map(|s| s.1).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut cov) = (0.0, 0.0, 0.0);
+            for (a, b) in &samples {
+                var_a += (a - mean_a) * (a - mean_a);
+                var_b += (b - mean_b) * (b - mean_b);
+                cov += (a - mean_a) * (b - mean_b);
+            }
+            let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+            sum += ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    if windows == 0 {
+        1.0
+    } else {
+        sum / f64::from(windows)
+    }
+}
+
+fn luma(pixel: &Rgb<u8>) -> f64 {
+    0.299 * f64::from(pixel[0]) + 0.587 * f64::from(pixel[1]) + 0.114 * f64::from(pixel[2])
+}
+
+/// 把像素转为变暗的灰度，作为差异图背景
+fn dimmed(pixel: &Rgb<u8>) -> Rgb<u8> {
+    let luma =
+        (u32::from(pixel[0]) * 299 + u32::from(pixel[1]) * 587 + u32::from(pixel[2]) * 114) / 1000;
+    let v = (luma / 3 + 170) as u8;
+    Rgb([v, v, v])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_images_pass() {
+        let img = RgbImage::from_pixel(10, 10, Rgb([10, 20, 30]));
+        let result = compare(&img, &img, DiffOptions::default(), &[]).unwrap();
+        assert!(result.passed);
+        assert_eq!(result.diff_pixels, 0);
+        assert_eq!(result.max_channel_delta, 0);
+        assert!((result.ssim - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tolerance_and_ratio() {
+        let baseline = RgbImage::from_pixel(10, 10, Rgb([100, 100, 100]));
+        let mut actual = baseline.clone();
+        actual.put_pixel(0, 0, Rgb([103, 100, 100]));
+        actual.put_pixel(1, 0, Rgb([200, 100, 100]));
+
+        let strict = DiffOptions {
+            tolerance: 2,
+            max_diff_ratio: 0.0,
+            ..DiffOptions::default()
+        };
+        let result = compare(&actual, &baseline, strict, &[]).unwrap();
+        assert_eq!(result.diff_pixels, 2);
+        assert!(!result.passed);
+        assert_eq!(*result.diff_image.get_pixel(0, 0), Rgb([255, 0, 0]));
+
+        let lenient = DiffOptions {
+            tolerance: 5,
+            max_diff_ratio: 0.01,
+            ..DiffOptions::default()
+        };
+        let result = compare(&actual, &baseline, lenient, &[]).unwrap();
+        assert_eq!(result.diff_pixels, 1);
+        assert!(result.passed);
+    }
+
+    #[test]
+    fn test_masked_region_is_ignored() {
+        let baseline = RgbImage::from_pixel(16, 16, Rgb([100, 100, 100]));
+        let mut actual = baseline.clone();
+        for y in 4..8 {
+            for x in 4..8 {
+                actual.put_pixel(x, y, Rgb([255, 0, 0]));
+            }
+        }
+        let strict = DiffOptions {
+            max_diff_ratio: 0.0,
+            ..DiffOptions::default()
+        };
+        assert!(!compare(&actual, &baseline, strict, &[]).unwrap().passed);
+
+        let mask = MaskRect {
+            x: 4,
+            y: 4,
+            width: 4,
+            height: 4,
+        };
+        let result = compare(&actual, &baseline, strict, &[mask]).unwrap();
+        assert!(result.passed);
+        assert_eq!(result.masked_pixels, 16);
+        assert_eq!(result.total_pixels, 16 * 16 - 16);
+        assert!((result.ssim - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ssim_tolerates_small_noise() {
+        let baseline = RgbImage::from_fn(32, 32, |x, y| {
+            let v = ((x * 8 + y * 4) % 256) as u8;
+            Rgb([v, v, v])
+        });
+        let noisy = RgbImage::from_fn(32, 32, |x, y| {
+            let p = baseline.get_pixel(x, y);
+            let v = if (x + y) % 2 == 0 {
+                p[0].saturating_add(6)
+            } else {
+                p[0].saturating_sub(6)
+            };
+            Rgb([v, v, v])
+        });
+        let options = DiffOptions {
+            metric: DiffMetric::Ssim,
+            ssim_threshold: 0.9,
+            ..DiffOptions::default()
+        };
+        let result = compare(&noisy, &baseline, options, &[]).unwrap();
+        assert!(result.diff_ratio > 0.5);
+        assert!(result.passed, "ssim = {}", result.ssim);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let a = RgbImage::new(10, 10);
+        let b = RgbImage::new(10, 11);
+        assert!(compare(&a, &b, DiffOptions::default(), &[]).is_err());
+    }
+}
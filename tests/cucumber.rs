@@ -1,4 +1,5 @@
 use backoff::ExponentialBackoffBuilder;
+use cucumber::event::ScenarioFinished;
 use cucumber::{given, then, when, StatsWriter, World};
 use serde_json::json;
 use std::time::Duration;
@@ -63,19 +64,26 @@ impl Drop for GameWorld {
 
 impl GameWorld {
     async fn take_screenshot(&mut self, step_name: &str, step_number: usize) {
+        // SCREENSHOTS_BY_RENDERER=1 时按渲染环境指纹分目录，游戏侧替换 {fingerprint}
+        let renderer_dir = if env_flag("SCREENSHOTS_BY_RENDERER") {
+            "/{fingerprint}"
+        } else {
+            ""
+        };
         let screenshot_path = format!(
-            "{}/step_{:02}_{}.png",
-            self.scenario_dir, step_number, step_name
+            "{}{}/step_{:02}_{}.png",
+            self.scenario_dir, renderer_dir, step_number, step_name
         );
 
-        self.screenshot(&screenshot_path).await;
+        // 等待画面稳定，避免截到按钮颜色过渡中的帧
+        self.screenshot(&screenshot_path, true).await;
     }
 
     fn health_endpoint(&self) -> String {
         format!("{}/health", self.base_url)
     }
 
-    /// 调用 MCP tools/call，返回解析后的工具结果 JSON
+    /// 调用 MCP tools/call，返回 structuredContent（图像结果没有时解析文本内容）
     async fn mcp_call(
         &self,
         tool: &str,
@@ -99,26 +107,46 @@ impl GameWorld {
         if let Some(err) = resp.get("error") {
             return Err(format!("MCP error: {}", err));
         }
-        let text = resp["result"]["content"][0]["text"]
-            .as_str()
-            .unwrap_or("{}");
-        serde_json::from_str(text).map_err(|e| format!("解析工具结果失败: {}", e))
+        let result = &resp["result"];
+        // 工具失败以 isError 结果返回，structuredContent 为 {"error": {code, message, details}}
+        if result["isError"].as_bool() == Some(true) {
+            let error = &result["structuredContent"]["error"];
+            return Err(format!(
+                "{} 失败 [{}]: {}",
+                tool,
+                error["code"].as_str().unwrap_or("unknown"),
+                error["message"].as_str().unwrap_or_default()
+            ));
+        }
+        if let Some(structured) = result.get("structuredContent") {
+            return Ok(structured.clone());
+        }
+        // 截图等图像结果没有 structuredContent，返回说明文本
+        let text = result["content"][0]["text"].as_str().unwrap_or_default();
+        Ok(serde_json::from_str(text).unwrap_or_else(|_| json!(text)))
     }
 
     async fn hover(&self, x: f32, y: f32) {
-        if let Err(e) = self.mcp_call("hover", json!({"x": x, "y": y})).await {
+        if let Err(e) = self
+            .mcp_call("hover", json!({"x": x, "y": y, "settle": "update"}))
+            .await
+        {
             eprintln!("hover 失败: {}", e);
         }
     }
 
     async fn click(&self, x: f32, y: f32) {
-        if let Err(e) = self.mcp_call("click", json!({"x": x, "y": y})).await {
+        if let Err(e) = self
+            .mcp_call("click", json!({"x": x, "y": y, "settle": "update"}))
+            .await
+        {
             eprintln!("click 失败: {}", e);
         }
     }
 
-    async fn screenshot(&self, path: &str) {
-        if let Err(e) = self.mcp_call("screenshot", json!({"path": path})).await {
+    async fn screenshot(&self, path: &str, stable: bool) {
+        let args = json!({ "path": path, "stable": stable });
+        if let Err(e) = self.mcp_call("screenshot", args).await {
             eprintln!("screenshot 失败: {}", e);
         }
     }
@@ -144,12 +172,24 @@ impl GameWorld {
         self.log_file_name = log_file_name;
         self.scenario_dir = scenario_dir;
 
-        let child = std::process::Command::new(&binary_path)
+        let mut command = std::process::Command::new(&binary_path);
+        command
             .arg("--test-mode")
             .env("TEST_PORT", self.test_port.to_string())
-            .env("TEST_LOG_FILE", &self.log_file_name)
-            .spawn()
-            .expect("启动游戏失败");
+            .env("TEST_LOG_FILE", &self.log_file_name);
+        // TEST_HEADLESS=1 时以无窗口模式启动（离屏渲染，无需 X server）
+        if env_flag("TEST_HEADLESS") {
+            command.arg("--headless");
+        }
+        // UPDATE_BASELINES=1 时视觉对比改为写入新的基准图
+        if env_flag("UPDATE_BASELINES") {
+            command.arg("--update-baselines");
+        }
+        // VIRTUAL_CURSOR=1 时在截图与录制中显示虚拟光标
+        if env_flag("VIRTUAL_CURSOR") {
+            command.arg("--virtual-cursor");
+        }
+        let child = command.spawn().expect("启动游戏失败");
 
         self.game_process = Some(child);
 
@@ -165,10 +205,47 @@ impl GameWorld {
         }
 
         if !ok {
-            panic!("游戏启动超时。\n\n请参考 .github/workflows/test.yml，安装 Linux 依赖，并用 xvfb-run 运行测试：\n\nsudo apt-get install ...（依赖列表见 test.yml）\nxvfb-run --auto-servernum --server-args=\"-screen 0 1024x768x24\" cargo test\n");
+            panic!("游戏启动超时。\n\n请参考 .github/workflows/test.yml，安装 Linux 依赖，并用 xvfb-run 运行测试：\n\nsudo apt-get install ...（依赖列表见 test.yml）\nxvfb-run --auto-servernum --server-args=\"-screen 0 1024x768x24\" cargo test\n\n或者在只有软件渲染器的环境中使用无窗口模式：\n\nTEST_HEADLESS=1 WGPU_BACKEND=gl cargo test\n");
+        }
+
+        if recording_mode().is_some() {
+            if let Err(e) = self.mcp_call("start_recording", json!({})).await {
+                eprintln!("start_recording 失败: {}", e);
+            }
+        }
+    }
+
+    /// 场景结束时停止录制：RECORD_SCENARIOS=failed 时只保留失败场景的录制
+    async fn finish_recording(&self, failed: bool) {
+        let Some(mode) = recording_mode() else {
+            return;
+        };
+        if self.game_process.is_none() {
+            return;
+        }
+        let discard = mode == "failed" && !failed;
+        if let Err(e) = self
+            .mcp_call("stop_recording", json!({ "discard": discard }))
+            .await
+        {
+            eprintln!("stop_recording 失败: {}", e);
         }
     }
 
+    /// 视觉对比；基准图不存在时步骤失败，基准图只在 UPDATE_BASELINES 模式下写入
+    async fn compare_screenshot(&self, args: serde_json::Value) -> serde_json::Value {
+        let verdict = self
+            .mcp_call("compare_screenshot", args)
+            .await
+            .expect("视觉对比失败");
+        assert!(
+            verdict["status"] != "baseline_missing",
+            "基准图不存在: {}（以 UPDATE_BASELINES=1 运行生成，检查后提交 tests/baselines/）",
+            verdict["baseline"]
+        );
+        verdict
+    }
+
     fn read_log(&mut self) {
         self.log_content = read_last_n_lines(&self.log_file_name, 100);
     }
@@ -244,7 +321,9 @@ async fn component_count_should_be(world: &mut GameWorld, count: usize, componen
         .await
         .expect("组件查询失败");
 
-    let counts = data.as_array().expect("component_counts 不是数组");
+    let counts = data["components"]
+        .as_array()
+        .expect("component_counts 缺少 components");
     let actual = counts
         .iter()
         .find(|v| v["name"].as_str() == Some(component_type.as_str()))
@@ -259,6 +338,97 @@ async fn component_count_should_be(world: &mut GameWorld, count: usize, componen
     world.take_screenshot("组件数量检查", 5).await;
 }
 
+#[then(expr = "画面应与基准图 {string} 一致")]
+async fn screen_should_match_baseline(world: &mut GameWorld, name: String) {
+    let verdict = world.compare_screenshot(json!({ "name": name })).await;
+
+    assert!(
+        verdict["passed"].as_bool().unwrap_or(false),
+        "画面与基准图 {} 不一致: {}",
+        name,
+        verdict
+    );
+}
+
+/// 用元素区域颜色的中位数判断，避免按钮文字等少量像素的干扰
+async fn assert_element_color(world: &GameWorld, id: &str, expected: &str, tolerance: f64) {
+    let probe = world
+        .mcp_call("probe_pixels", json!({ "id": id }))
+        .await
+        .expect("像素探测失败");
+    let actual: Vec<f64> = probe["regions"][0]["median"]["srgb"]
+        .as_array()
+        .unwrap_or_else(|| panic!("像素探测结果无效: {}", probe))
+        .iter()
+        .filter_map(|v| v.as_f64())
+        .collect();
+    let expected_rgb = parse_color(expected);
+    let within = actual.len() == 3
+        && actual
+            .iter()
+            .zip(expected_rgb)
+            .all(|(a, e)| (a - e).abs() <= tolerance);
+    assert!(
+        within,
+        "元素 {} 的颜色不匹配: 期望 {}（容差 {}），实际 {:?}",
+        id, expected, tolerance, actual
+    );
+}
+
+#[then(expr = "元素 {string} 的颜色应接近 {string}")]
+async fn element_color_should_be(world: &mut GameWorld, id: String, expected: String) {
+    assert_element_color(world, &id, &expected, 0.02).await;
+}
+
+#[then(expr = "元素 {string} 的颜色应接近 {string}，容差 {float}")]
+async fn element_color_should_be_within(
+    world: &mut GameWorld,
+    id: String,
+    expected: String,
+    tolerance: f64,
+) {
+    assert_element_color(world, &id, &expected, tolerance).await;
+}
+
+/// 忽略随机内容（如小球）后按结构相似度对比，`ignored` 为逗号分隔的 TestId
+#[then(expr = "忽略 {string} 后画面应与基准图 {string} 相似")]
+async fn screen_should_resemble_baseline(world: &mut GameWorld, ignored: String, name: String) {
+    let mask: Vec<&str> = ignored.split(',').map(str::trim).collect();
+    let verdict = world
+        .compare_screenshot(json!({ "name": name, "mask": mask, "metric": "ssim" }))
+        .await;
+
+    assert!(
+        verdict["passed"].as_bool().unwrap_or(false),
+        "画面与基准图 {} 不相似: {}",
+        name,
+        verdict
+    );
+}
+
+/// 解析期望颜色："0.3,0.5,0.7"（sRGB 0-1，与 Color::srgb 一致）或 "#4d80b3"
+fn parse_color(color: &str) -> [f64; 3] {
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16).expect("无效的十六进制颜色") as f64 / 255.0
+        };
+        return [channel(0), channel(2), channel(4)];
+    }
+    let parts: Vec<f64> = color
+        .split(',')
+        .map(|v| v.trim().parse().expect("无效的颜色分量"))
+        .collect();
+    assert_eq!(parts.len(), 3, "颜色应为 r,g,b 三个分量: {}", color);
+    [parts[0], parts[1], parts[2]]
+}
+
+/// 场景录制模式（RECORD_SCENARIOS 环境变量）："failed" 只保留失败场景，"all" 保留全部
+fn recording_mode() -> Option<String> {
+    std::env::var("RECORD_SCENARIOS")
+        .ok()
+        .filter(|mode| mode == "failed" || mode == "all")
+}
+
 #[tokio::main]
 async fn main() {
     let result = GameWorld::cucumber()
@@ -289,6 +459,17 @@ async fn main() {
                 world.scenario_name = scenario_name;
             })
         })
+        .after(|_feature, _rule, _scenario, finished, world| {
+            Box::pin(async move {
+                if let Some(world) = world {
+                    let failed = matches!(
+                        finished,
+                        ScenarioFinished::StepFailed(..) | ScenarioFinished::BeforeHookFailed(_)
+                    );
+                    world.finish_recording(failed).await;
+                }
+            })
+        })
         .run("tests/features/")
         .await;
 
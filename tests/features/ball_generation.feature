@@ -7,3 +7,11 @@
     当 点击按钮 "main-button"
     那么 日志中应该包含 "生成小球在位置"
     而且 存在 1 个类型为 "Ball" 的组件
+    而且 元素 "ball" 的颜色应接近 "1.0,0.3,0.3"
+    而且 元素 "ball" 的颜色应接近 "#ff4d4d"，容差 0.03
+
+  场景: 小球位置随机时画面仍与基准图相似
+    假设 游戏已启动
+    当 点击按钮 "main-button"
+    那么 存在 1 个类型为 "Ball" 的组件
+    而且 忽略 "ball" 后画面应与基准图 "ball-generated" 相似
@@ -8,3 +8,7 @@
     假设 游戏已启动
     当 点击按钮 "main-button"
     那么 日志中应该包含 "test-id-button-clicked: main-button"
+
+  场景: 启动画面与基准图一致
+    假设 游戏已启动
+    那么 画面应与基准图 "start" 一致
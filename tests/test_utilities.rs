@@ -42,6 +42,17 @@ pub fn get_binary_path(project_name: &str, target_dir: &str) -> PathBuf {
     PathBuf::from(target_dir).join("debug").join(&exe_name)
 }
 
+/// 读取开关类环境变量：只有 "1" 或 "true"（不区分大小写）视为开启，
+/// 未设置、空值或 "0" / "false" 等均为关闭
+pub fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| {
+            let value = value.trim();
+            value == "1" || value.eq_ignore_ascii_case("true")
+        })
+        .unwrap_or(false)
+}
+
 /// 查找一个可用的端口
 pub fn find_available_port() -> u16 {
     // 绑定到端口 0 让操作系统自动分配空闲端口